@@ -4,12 +4,13 @@ use std::ops::{BitAnd, BitOr, Shl, Shr};
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use ibig::{UBig, ubig};
-use image::RgbaImage;
+use image::{Rgba, RgbaImage};
 use itertools::Itertools;
 
+use crate::layout::skin_scale;
 use crate::parser::utils::to_argb_hex;
 use crate::utils::errors::{EarsError, Result};
-use crate::utils::model::{AlfalfaData, Rectangle};
+use crate::utils::model::{AlfalfaDamage, AlfalfaData, PartialAlfalfaData, Rectangle};
 
 const ENCODE_REGIONS: [Rectangle; 10] = [
     Rectangle {
@@ -74,29 +75,49 @@ const ENCODE_REGIONS: [Rectangle; 10] = [
     },
 ];
 
-const MAGIC: u32 = 0xEA1FA1FA; // EALFALFA
+pub(super) const MAGIC: u32 = 0xEA1FA1FA; // EALFALFA
 const PREDEF_KEYS: [&str; 4] = ["END", "wing", "erase", "cape"];
 
 pub fn read_alfalfa(image: &RgbaImage) -> Result<Option<AlfalfaData>> {
-    let Some(data) = decode_alfalfa(image)? else {
+    match read_alfalfa_partial(image)? {
+        Some(PartialAlfalfaData {
+            damage: Some(damage),
+            ..
+        }) => Err(EarsError::DamagedAlfalfaData(damage)),
+        partial => Ok(partial.map(|partial| partial.data)),
+    }
+}
+
+/// Reads the Alfalfa payload of `image`, keeping every entry decoded before the payload turned
+/// out to be damaged instead of failing the whole read.
+///
+/// Returns `None` if the image does not carry Alfalfa data at all.
+pub fn read_alfalfa_partial(image: &RgbaImage) -> Result<Option<PartialAlfalfaData>> {
+    let Some(decoded) = decode_alfalfa(image)? else {
         return Ok(None);
     };
     // Opaque feature pixels can overlap Alfalfa's encoding regions. They are not an Alfalfa
     // payload unless they contain the complete magic value.
-    if data.len() < std::mem::size_of::<u32>() {
+    if decoded.bytes.len() < std::mem::size_of::<u32>() {
         return Ok(None);
     }
-    let mut data = Cursor::new(data);
+    let mut reader = PayloadReader::new(&decoded);
 
-    let magic = data
-        .read_u32::<BigEndian>()
-        .map_err(|e| (e, "Unable to read Magic data"))?;
+    let magic = reader.read_u32().map_err(EarsError::DamagedAlfalfaData)?;
 
     if magic != MAGIC {
         return Ok(None);
     }
 
-    let version = data.read_u8().map_err(|e| (e, "Unable to read version"))?;
+    let version = match reader.read_u8("Unable to read version") {
+        Ok(version) => version,
+        Err(damage) => {
+            return Ok(Some(PartialAlfalfaData {
+                data: AlfalfaData::new(),
+                damage: Some(damage),
+            }));
+        }
+    };
 
     if version != 1 {
         // Don't know how to read this version, ignoring
@@ -104,11 +125,20 @@ pub fn read_alfalfa(image: &RgbaImage) -> Result<Option<AlfalfaData>> {
     }
 
     let mut map = HashMap::with_capacity(PREDEF_KEYS.len());
+    let damage = read_alfalfa_entries(&mut reader, &mut map).err();
 
+    Ok(Some(PartialAlfalfaData {
+        data: AlfalfaData { version, data: map },
+        damage,
+    }))
+}
+
+fn read_alfalfa_entries(
+    data: &mut PayloadReader,
+    map: &mut HashMap<String, Vec<u8>>,
+) -> core::result::Result<(), AlfalfaDamage> {
     loop {
-        let index = data
-            .read_u8()
-            .map_err(|e| (e, "Unable to read alfalfa key index"))?;
+        let index = data.read_u8("Unable to read alfalfa key index")?;
         let key = if index < 64 {
             if (index as usize) < PREDEF_KEYS.len() {
                 PREDEF_KEYS[index as usize].to_string()
@@ -120,9 +150,7 @@ pub fn read_alfalfa(image: &RgbaImage) -> Result<Option<AlfalfaData>> {
             out.push(index as char);
 
             loop {
-                let b = data
-                    .read_u8()
-                    .map_err(|e| (e, "Unable to read alfalfa key (2)"))?;
+                let b = data.read_u8("Unable to read alfalfa key (2)")?;
 
                 if (b & 0x80) != 0 {
                     out.push((b & 0x7F) as char);
@@ -143,14 +171,17 @@ pub fn read_alfalfa(image: &RgbaImage) -> Result<Option<AlfalfaData>> {
 
         loop {
             let len = data
-                .read_u8()
-                .map_err(|e| (e, "Unable to read data length"))?;
+                .read_u8("Unable to read data length")
+                .map_err(|damage| damage.in_entry(&key))?;
             // Read len bytes into the end of the buffer
             let old_len = buf.len();
             let new_len = old_len + len as usize;
             buf.resize(new_len, 0);
-            data.read_exact(&mut buf[old_len..new_len])
-                .map_err(|e| (e, "Unable to read alfalfa data into buffer"))?;
+            data.read_exact(
+                &mut buf[old_len..new_len],
+                "Unable to read alfalfa data into buffer",
+            )
+            .map_err(|damage| damage.in_entry(&key))?;
 
             if len != 255 {
                 break;
@@ -160,20 +191,91 @@ pub fn read_alfalfa(image: &RgbaImage) -> Result<Option<AlfalfaData>> {
         map.insert(key, buf);
     }
 
-    Ok(Some(AlfalfaData { version, data: map }))
+    Ok(())
 }
 
-fn decode_alfalfa(image: &RgbaImage) -> Result<Option<Vec<u8>>> {
-    if image.width() != 64 || image.height() != 64 {
-        return Ok(None);
+/// Reads an Alfalfa payload while keeping track of where each byte came from in the skin.
+struct PayloadReader<'a> {
+    decoded: &'a DecodedAlfalfa,
+    cursor: Cursor<&'a [u8]>,
+}
+
+impl<'a> PayloadReader<'a> {
+    fn new(decoded: &'a DecodedAlfalfa) -> Self {
+        Self {
+            decoded,
+            cursor: Cursor::new(decoded.bytes.as_slice()),
+        }
+    }
+
+    fn read_u32(&mut self) -> core::result::Result<u32, AlfalfaDamage> {
+        let offset = self.cursor.position() as usize;
+        self.cursor
+            .read_u32::<BigEndian>()
+            .map_err(|_| self.damage(offset, "Unable to read Magic data"))
+    }
+
+    fn read_u8(&mut self, reason: &'static str) -> core::result::Result<u8, AlfalfaDamage> {
+        let offset = self.cursor.position() as usize;
+        self.cursor
+            .read_u8()
+            .map_err(|_| self.damage(offset, reason))
+    }
+
+    fn read_exact(
+        &mut self,
+        buf: &mut [u8],
+        reason: &'static str,
+    ) -> core::result::Result<(), AlfalfaDamage> {
+        let offset = self.cursor.position() as usize;
+        self.cursor
+            .read_exact(buf)
+            .map_err(|_| self.damage(offset, reason))
     }
 
+    fn damage(&self, offset: usize, reason: &'static str) -> AlfalfaDamage {
+        AlfalfaDamage {
+            offset,
+            pixel: self.decoded.pixel_of_byte(offset),
+            entry: None,
+            reason,
+        }
+    }
+}
+
+/// The raw bytes of an Alfalfa payload, along with the skin pixel each 7-bit group was read from.
+struct DecodedAlfalfa {
+    bytes: Vec<u8>,
+    pixels: Vec<(u32, u32)>,
+}
+
+impl DecodedAlfalfa {
+    /// Maps a byte offset in the payload back to the pixel holding its most significant bit.
+    ///
+    /// The payload is stored as a big-endian number starting at the first pixel, so the first
+    /// bytes of the payload live in the last pixels that were read.
+    fn pixel_of_byte(&self, offset: usize) -> Option<(u32, u32)> {
+        let remaining = self.bytes.len().checked_sub(offset)?;
+        if remaining == 0 {
+            return None;
+        }
+        let bit = remaining * 8 - 1;
+        self.pixels.get(bit / 7).copied()
+    }
+}
+
+fn decode_alfalfa(image: &RgbaImage) -> Result<Option<DecodedAlfalfa>> {
+    let Some(scale) = skin_scale(image) else {
+        return Ok(None);
+    };
+
     let mut bi = ubig!(0);
-    let mut read = 0u32;
+    let mut pixels = Vec::new();
 
     for rect in ENCODE_REGIONS {
         for x in rect.x1..rect.x2 {
             for y in rect.y1..rect.y2 {
+                let (x, y) = (x * scale, y * scale);
                 let pixel = image
                     .get_pixel_checked(x, y)
                     .ok_or(EarsError::InvalidAlfalfaPixelPosition(x, y))?;
@@ -184,14 +286,18 @@ fn decode_alfalfa(image: &RgbaImage) -> Result<Option<Vec<u8>>> {
 
                 let value = 0x7F - (a & 0x7F);
 
-                bi = bi.bitor(UBig::from(value).shl(read as usize * 7usize));
-                read += 1;
+                bi = bi.bitor(UBig::from(value).shl(pixels.len() * 7usize));
+                pixels.push((x, y));
             }
         }
     }
 
-    let vec = bi.to_be_bytes();
-    Ok(if vec.is_empty() { None } else { Some(vec) })
+    let bytes = bi.to_be_bytes();
+    Ok(if bytes.is_empty() {
+        None
+    } else {
+        Some(DecodedAlfalfa { bytes, pixels })
+    })
 }
 
 pub fn encode_alfalfa(data: &AlfalfaData, out: &mut Vec<u8>) -> Result<()> {
@@ -253,41 +359,149 @@ pub fn encode_alfalfa(data: &AlfalfaData, out: &mut Vec<u8>) -> Result<()> {
     Ok(())
 }
 
+/// How [`write_alfalfa_with_mode`] picks the pixels that carry the Alfalfa payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlfalfaWriteMode {
+    /// Matches the Ears mod: every transparent pixel in the encoding regions becomes opaque black.
+    #[default]
+    Standard,
+    /// Keeps transparent pixels transparent whenever the payload fits in the remaining pixels,
+    /// only making as many of them opaque as the payload needs. Fewer pixels change for
+    /// clients that don't know about Ears.
+    Packed,
+}
+
+/// A pixel that writing Alfalfa data would change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlfalfaPixelChange {
+    pub x: u32,
+    pub y: u32,
+    pub before: Rgba<u8>,
+    pub after: Rgba<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlfalfaPixelChangeKind {
+    /// A transparent pixel is replaced with opaque black.
+    MadeOpaque,
+    /// Only the alpha value of the pixel changes.
+    AlphaChanged,
+}
+
+impl AlfalfaPixelChange {
+    pub fn kind(&self) -> AlfalfaPixelChangeKind {
+        if self.before[3] == 0 {
+            AlfalfaPixelChangeKind::MadeOpaque
+        } else {
+            AlfalfaPixelChangeKind::AlphaChanged
+        }
+    }
+}
+
 pub fn write_alfalfa(data: &AlfalfaData, image: &mut RgbaImage) -> Result<()> {
+    write_alfalfa_with_mode(data, image, AlfalfaWriteMode::Standard)
+}
+
+pub fn write_alfalfa_with_mode(
+    data: &AlfalfaData,
+    image: &mut RgbaImage,
+    mode: AlfalfaWriteMode,
+) -> Result<()> {
     let mut buf = Vec::with_capacity(1428);
     encode_alfalfa(data, &mut buf)?;
 
+    write_alfalfa_bytes(&buf, image, mode)
+}
+
+/// Returns the pixels that [`write_alfalfa_with_mode`] would change, without touching `image`.
+pub fn preview_alfalfa_write(
+    data: &AlfalfaData,
+    image: &RgbaImage,
+    mode: AlfalfaWriteMode,
+) -> Result<Vec<AlfalfaPixelChange>> {
+    let mut written = image.clone();
+    write_alfalfa_with_mode(data, &mut written, mode)?;
+
+    Ok(image
+        .enumerate_pixels()
+        .zip(written.pixels())
+        .filter(|((_, _, before), after)| before != after)
+        .map(|((x, y, &before), &after)| AlfalfaPixelChange {
+            x,
+            y,
+            before,
+            after,
+        })
+        .collect())
+}
+
+pub(super) fn write_alfalfa_bytes(
+    buf: &[u8],
+    image: &mut RgbaImage,
+    mode: AlfalfaWriteMode,
+) -> Result<()> {
     if buf.len() > 1428 {
         return Err(EarsError::AlfalfaDataTooLarge(buf.len()));
     }
 
-    let bi = ibig::UBig::from_be_bytes(buf.as_slice());
+    let Some(scale) = skin_scale(image) else {
+        return Err(EarsError::InvalidAlfalfaImageSize(
+            image.width(),
+            image.height(),
+        ));
+    };
+
+    let texels = ENCODE_REGIONS
+        .iter()
+        .flat_map(|rect| (rect.x1..rect.x2).cartesian_product(rect.y1..rect.y2))
+        .map(|(x, y)| {
+            let (x, y) = (x * scale, y * scale);
+            let pixel = image
+                .get_pixel_checked(x, y)
+                .ok_or(EarsError::InvalidAlfalfaPixelPosition(x, y))?;
+            Ok((x, y, pixel[3] == 0))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let bi = ibig::UBig::from_be_bytes(buf);
+    let groups = bi.bit_len().div_ceil(7) as u32;
+    let mut opaque_left = texels
+        .iter()
+        .filter(|(_, _, transparent)| !transparent)
+        .count() as u32;
     let mut written = 0u32;
 
-    for rect in ENCODE_REGIONS {
-        for x in rect.x1..rect.x2 {
-            for y in rect.y1..rect.y2 {
-                let pixel = image
-                    .get_pixel_mut_checked(x, y)
-                    .ok_or(EarsError::InvalidAlfalfaPixelPosition(x, y))?;
-                let a = to_argb_hex(pixel) >> 24 & 0xFF;
-                if a == 0 {
-                    *pixel = image::Rgba([0, 0, 0, 0xFF]);
-                }
+    for (x, y, transparent) in texels {
+        if !transparent {
+            opaque_left -= 1;
+        } else if mode == AlfalfaWriteMode::Packed && groups.saturating_sub(written) <= opaque_left
+        {
+            // The reader skips transparent pixels, so the rest of the payload can go elsewhere
+            continue;
+        }
 
-                let _7f = ubig!(0x7F);
-
-                let v: u32 = bi
-                    .clone()
-                    .shr((written * 7) as usize)
-                    .bitand(_7f)
-                    .try_into()
-                    .map_err(|_| EarsError::UnableToConvertBigUintToU32)?;
-                let a = (0x7F - v) | 0x80;
-                *pixel = image::Rgba([pixel[0], pixel[1], pixel[2], a as u8]);
-                written += 1;
+        let _7f = ubig!(0x7F);
+
+        let v: u32 = bi
+            .clone()
+            .shr((written * 7) as usize)
+            .bitand(_7f)
+            .try_into()
+            .map_err(|_| EarsError::UnableToConvertBigUintToU32)?;
+        let a = (0x7F - v) | 0x80;
+
+        // Every texel of the scaled block carries the same alpha, so the block keeps a
+        // uniform look once the alpha is stripped.
+        for (x, y) in (x..x + scale).cartesian_product(y..y + scale) {
+            let pixel = image
+                .get_pixel_mut_checked(x, y)
+                .ok_or(EarsError::InvalidAlfalfaPixelPosition(x, y))?;
+            if pixel[3] == 0 {
+                *pixel = image::Rgba([0, 0, 0, 0xFF]);
             }
+            *pixel = image::Rgba([pixel[0], pixel[1], pixel[2], a as u8]);
         }
+        written += 1;
     }
 
     Ok(())
@@ -295,6 +509,10 @@ pub fn write_alfalfa(data: &AlfalfaData, image: &mut RgbaImage) -> Result<()> {
 
 #[cfg(test)]
 mod tests {
+    use image::imageops::FilterType;
+
+    use crate::alfalfa::AlfalfaDataKey;
+    use crate::alfalfa::test_support::write_damaged_alfalfa;
     use crate::utils::strip_alpha;
 
     use super::*;
@@ -317,6 +535,153 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn alfalfa_read_works_on_hd_skins() -> Result<()> {
+        let image = image::open("test_images/ears_v1_nickac_sample.png").unwrap();
+        let image = image.to_rgba8();
+        let hd_image = image::imageops::resize(&image, 128, 128, FilterType::Nearest);
+
+        assert_eq!(read_alfalfa(&hd_image)?, read_alfalfa(&image)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn alfalfa_write_works_roundtrip_on_hd_skins() -> Result<()> {
+        let image = image::open("test_images/ears_v1_nickac_sample.png").unwrap();
+        let image = image.to_rgba8();
+
+        let map = read_alfalfa(&image)?.unwrap();
+
+        let mut out_image = RgbaImage::new(256, 256);
+        write_alfalfa(&map, &mut out_image)?;
+
+        assert_eq!(read_alfalfa(&out_image)?, Some(map));
+
+        Ok(())
+    }
+
+    #[test]
+    fn alfalfa_write_rejects_non_square_skins() {
+        let mut image = RgbaImage::new(64, 32);
+
+        assert!(matches!(
+            write_alfalfa(&AlfalfaData::new(), &mut image),
+            Err(EarsError::InvalidAlfalfaImageSize(64, 32))
+        ));
+    }
+
+    #[test]
+    fn alfalfa_partial_read_keeps_entries_before_damage() -> Result<()> {
+        let mut image = RgbaImage::new(64, 64);
+        write_damaged_alfalfa(&mut image)?;
+
+        let partial = read_alfalfa_partial(&image)?.unwrap();
+
+        assert_eq!(
+            partial.data.get_data_raw(),
+            &HashMap::from([(
+                "erase".to_string(),
+                vec![196, 131, 30, 2, 12, 122, 141, 24, 96, 152, 201]
+            )])
+        );
+        assert_eq!(
+            partial.damage,
+            Some(AlfalfaDamage {
+                offset: 20,
+                pixel: Some((8, 5)),
+                entry: Some("cape".to_string()),
+                reason: "Unable to read alfalfa data into buffer",
+            })
+        );
+        assert!(matches!(
+            read_alfalfa(&image),
+            Err(EarsError::DamagedAlfalfaData(damage)) if partial.damage.as_ref() == Some(&damage)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn alfalfa_partial_read_reports_no_damage_for_intact_data() -> Result<()> {
+        let image = image::open("test_images/ears_v1_nickac_sample.png").unwrap();
+        let image = image.to_rgba8();
+
+        let partial = read_alfalfa_partial(&image)?.unwrap();
+
+        assert_eq!(partial.damage, None);
+        assert_eq!(Some(partial.data), read_alfalfa(&image)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn alfalfa_packed_write_keeps_transparent_pixels() -> Result<()> {
+        let image = image::open("test_images/ears_v1_nickac_sample.png").unwrap();
+        let mut image = image.to_rgba8();
+        for y in 20..32 {
+            for x in 0..56 {
+                image.put_pixel(x, y, Rgba([12, 34, 56, 0]));
+            }
+        }
+        let mut data = AlfalfaData::new();
+        data.set_data(
+            AlfalfaDataKey::Erase,
+            vec![196, 131, 30, 2, 12, 122, 141, 24, 96, 152, 201],
+        );
+
+        let standard = preview_alfalfa_write(&data, &image, AlfalfaWriteMode::Standard)?;
+        let packed = preview_alfalfa_write(&data, &image, AlfalfaWriteMode::Packed)?;
+
+        assert!(
+            standard
+                .iter()
+                .any(|change| change.kind() == AlfalfaPixelChangeKind::MadeOpaque)
+        );
+        assert!(
+            packed
+                .iter()
+                .all(|change| change.kind() == AlfalfaPixelChangeKind::AlphaChanged)
+        );
+        assert!(packed.len() < standard.len());
+
+        write_alfalfa_with_mode(&data, &mut image, AlfalfaWriteMode::Packed)?;
+
+        assert_eq!(*image.get_pixel(0, 20), Rgba([12, 34, 56, 0]));
+        assert_eq!(read_alfalfa(&image)?, Some(data));
+
+        Ok(())
+    }
+
+    #[test]
+    fn alfalfa_write_preview_matches_write() -> Result<()> {
+        let image = image::open("test_images/ears_v1_nickac_sample.png").unwrap();
+        let image = image.to_rgba8();
+        let mut data = read_alfalfa(&image)?.unwrap();
+        data.remove_data(AlfalfaDataKey::Cape);
+
+        let changes = preview_alfalfa_write(&data, &image, AlfalfaWriteMode::Standard)?;
+
+        let mut written = image.clone();
+        write_alfalfa(&data, &mut written)?;
+
+        assert!(!changes.is_empty());
+        for change in &changes {
+            assert_eq!(*image.get_pixel(change.x, change.y), change.before);
+            assert_eq!(*written.get_pixel(change.x, change.y), change.after);
+        }
+        assert_eq!(
+            changes.len(),
+            image
+                .pixels()
+                .zip(written.pixels())
+                .filter(|(a, b)| a != b)
+                .count()
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn alfalfa_read_works() -> Result<()> {
         let image = image::open("test_images/ears_v1_nickac_sample.png").unwrap();
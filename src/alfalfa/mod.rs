@@ -1,7 +1,14 @@
 mod io;
+#[cfg(test)]
+pub(crate) mod test_support;
 pub mod utils;
 
 pub use io::read_alfalfa;
+pub use io::read_alfalfa_partial;
 pub use io::write_alfalfa;
+pub use io::{
+    AlfalfaPixelChange, AlfalfaPixelChangeKind, AlfalfaWriteMode, preview_alfalfa_write,
+    write_alfalfa_with_mode,
+};
 
-pub use crate::utils::model::{AlfalfaData, AlfalfaDataKey};
+pub use crate::utils::model::{AlfalfaDamage, AlfalfaData, AlfalfaDataKey, PartialAlfalfaData};
@@ -0,0 +1,21 @@
+//! Helpers for tests that need Alfalfa data no writer would produce.
+
+use byteorder::{BigEndian, WriteBytesExt};
+use image::RgbaImage;
+
+use super::AlfalfaWriteMode;
+use super::io::{MAGIC, write_alfalfa_bytes};
+use crate::utils::errors::Result;
+
+/// Writes a payload whose erase entry is complete, followed by a cape entry claiming more bytes
+/// than the payload holds.
+pub(crate) fn write_damaged_alfalfa(image: &mut RgbaImage) -> Result<()> {
+    let mut buf = Vec::new();
+    buf.write_u32::<BigEndian>(MAGIC)
+        .map_err(|e| (e, "Unable to write alfalfa magic"))?;
+    buf.push(1);
+    buf.extend([2, 11, 196, 131, 30, 2, 12, 122, 141, 24, 96, 152, 201]);
+    buf.extend([3, 200, 137, 80, 78, 71, 13]);
+
+    write_alfalfa_bytes(&buf, image, AlfalfaWriteMode::Standard)
+}
@@ -0,0 +1,137 @@
+use crate::alfalfa::utils::EraseRegion;
+
+/// A 64x64 mask of the skin pixels that should be erased, indexed as `mask[y][x]`.
+pub type EraseMask = [[bool; 64]; 64];
+
+/// The largest width or height an [`EraseRegion`] can encode.
+const MAX_REGION_SIZE: usize = 32;
+
+/// Converts `mask` into a short list of [`EraseRegion`]s that together erase exactly the masked
+/// pixels.
+///
+/// Regions are picked greedily: starting from the first pixel that isn't covered yet, the
+/// rectangle covering the most uncovered pixels is chosen. Regions may overlap each other, but
+/// never cover unmasked pixels, and areas wider or taller than an erase region can encode are
+/// split up.
+pub fn erase_regions_from_mask(mask: &EraseMask) -> Vec<EraseRegion> {
+    let mut covered = [[false; 64]; 64];
+    let mut regions = Vec::new();
+
+    for y in 0..64 {
+        for x in 0..64 {
+            if !mask[y][x] || covered[y][x] {
+                continue;
+            }
+
+            let (width, height) = best_region_at(mask, &covered, x, y);
+            for row in covered.iter_mut().skip(y).take(height) {
+                row[x..x + width].fill(true);
+            }
+
+            regions.push(EraseRegion {
+                x: x as u8,
+                y: y as u8,
+                width: width as u8,
+                height: height as u8,
+            });
+        }
+    }
+
+    regions
+}
+
+/// Finds the size of the masked rectangle with its top-left corner at (`x`, `y`) that covers the
+/// most pixels which aren't covered yet.
+fn best_region_at(
+    mask: &EraseMask,
+    covered: &[[bool; 64]; 64],
+    x: usize,
+    y: usize,
+) -> (usize, usize) {
+    let max_width = mask[y][x..]
+        .iter()
+        .take(MAX_REGION_SIZE)
+        .take_while(|&&masked| masked)
+        .count();
+
+    let mut best = (1, 1);
+    let mut best_score = 0;
+    for width in 1..=max_width {
+        let height = mask[y..]
+            .iter()
+            .take(MAX_REGION_SIZE)
+            .take_while(|row| row[x..x + width].iter().all(|&masked| masked))
+            .count();
+        let score = covered[y..y + height]
+            .iter()
+            .map(|row| row[x..x + width].iter().filter(|&&c| !c).count())
+            .sum::<usize>();
+
+        if score > best_score {
+            best = (width, height);
+            best_score = score;
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::alfalfa::read_alfalfa;
+    use crate::alfalfa::utils::EraseRegionsProvider;
+    use crate::utils::errors::Result;
+
+    fn mask_of(regions: &[EraseRegion]) -> EraseMask {
+        let mut mask = [[false; 64]; 64];
+        for region in regions {
+            for row in mask
+                .iter_mut()
+                .skip(region.y as usize)
+                .take(region.height as usize)
+            {
+                row[region.x as usize..(region.x + region.width) as usize].fill(true);
+            }
+        }
+        mask
+    }
+
+    #[test]
+    fn full_mask_is_split_into_encodable_regions() {
+        let regions = erase_regions_from_mask(&[[true; 64]; 64]);
+
+        assert_eq!(regions.len(), 4);
+        assert!(
+            regions
+                .iter()
+                .all(|region| region.width == 32 && region.height == 32)
+        );
+        assert_eq!(mask_of(&regions), [[true; 64]; 64]);
+    }
+
+    #[test]
+    fn empty_mask_has_no_regions() {
+        assert!(erase_regions_from_mask(&[[false; 64]; 64]).is_empty());
+    }
+
+    #[test]
+    fn mask_regions_erase_exactly_the_mask() -> Result<()> {
+        let image = image::open("test_images/ears_v1_nickac_sample.png").unwrap();
+        let image = image.to_rgba8();
+        let original = read_alfalfa(&image)?.unwrap().get_erase_regions()?.unwrap();
+
+        let mut mask = mask_of(&original);
+        // An L-shaped area that needs more than one rectangle
+        for (y, row) in mask.iter_mut().enumerate().skip(50) {
+            row[2..if y < 55 { 12 } else { 4 }].fill(true);
+        }
+
+        let regions = erase_regions_from_mask(&mask);
+
+        assert_eq!(mask_of(&regions), mask);
+        assert!(regions.len() <= original.len() + 2);
+
+        Ok(())
+    }
+}
@@ -1,6 +1,6 @@
 use std::io::{Cursor, Read, Write};
 
-use crate::utils::errors::Result;
+use crate::utils::errors::{EarsError, Result};
 use crate::utils::model::{AlfalfaData, AlfalfaDataKey};
 use crate::utils::{bit_reader::BitReader, bit_writer::BitWriter};
 
@@ -13,6 +13,38 @@ pub struct EraseRegion {
 }
 
 impl EraseRegion {
+    /// The largest x or y position an erase region can start at (6 bits).
+    pub const MAX_POSITION: u8 = 63;
+    /// The largest width or height an erase region can have (5 bits, stored minus one).
+    pub const MAX_SIZE: u8 = 32;
+
+    pub fn new(x: u8, y: u8, width: u8, height: u8) -> Result<EraseRegion> {
+        let region = EraseRegion {
+            x,
+            y,
+            width,
+            height,
+        };
+        region.validate()?;
+
+        Ok(region)
+    }
+
+    /// Checks that the region survives being encoded, since out of range values would otherwise
+    /// be truncated into a different rectangle.
+    pub fn validate(&self) -> Result<()> {
+        if self.x > Self::MAX_POSITION || self.y > Self::MAX_POSITION {
+            return Err(EarsError::InvalidEraseRegionPosition(self.x, self.y));
+        }
+        if !(1..=Self::MAX_SIZE).contains(&self.width)
+            || !(1..=Self::MAX_SIZE).contains(&self.height)
+        {
+            return Err(EarsError::InvalidEraseRegionSize(self.width, self.height));
+        }
+
+        Ok(())
+    }
+
     fn decode<R: Read>(reader: &mut BitReader<R>) -> Result<EraseRegion> {
         let x = reader.read(6)? as u8;
         let y = reader.read(6)? as u8;
@@ -29,6 +61,8 @@ impl EraseRegion {
     }
 
     fn encode<W: Write>(&self, writer: &mut BitWriter<W>) -> Result<()> {
+        self.validate()?;
+
         writer.write_long(6, self.x as u64)?;
         writer.write_long(6, self.y as u64)?;
 
@@ -72,6 +106,10 @@ impl EraseRegionsProvider for AlfalfaData {
     }
 
     fn set_erase_regions(&mut self, regions: &[EraseRegion]) -> Result<()> {
+        for region in regions {
+            region.validate()?;
+        }
+
         let mut data = Vec::new();
         {
             let mut writer = BitWriter::new(Cursor::new(&mut data));
@@ -177,4 +215,39 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn erase_region_new_rejects_unencodable_regions() {
+        assert!(EraseRegion::new(63, 63, 32, 32).is_ok());
+        assert!(matches!(
+            EraseRegion::new(0, 0, 0, 4),
+            Err(EarsError::InvalidEraseRegionSize(0, 4))
+        ));
+        assert!(matches!(
+            EraseRegion::new(0, 0, 4, 33),
+            Err(EarsError::InvalidEraseRegionSize(4, 33))
+        ));
+        assert!(matches!(
+            EraseRegion::new(64, 0, 4, 4),
+            Err(EarsError::InvalidEraseRegionPosition(64, 0))
+        ));
+    }
+
+    #[test]
+    fn set_erase_regions_rejects_unencodable_regions() -> Result<()> {
+        let mut data = AlfalfaData::new();
+        let valid = EraseRegion::new(1, 2, 3, 4)?;
+        let invalid = EraseRegion { width: 0, ..valid };
+
+        assert!(matches!(
+            data.set_erase_regions(&[valid, invalid]),
+            Err(EarsError::InvalidEraseRegionSize(0, 4))
+        ));
+        assert_eq!(data.get_erase_regions()?, None);
+
+        data.set_erase_regions(&[valid])?;
+        assert_eq!(data.get_erase_regions()?, Some(vec![valid]));
+
+        Ok(())
+    }
 }
@@ -1,3 +1,5 @@
+mod erase_mask;
 mod erase_utils;
 
+pub use erase_mask::{EraseMask, erase_regions_from_mask};
 pub use erase_utils::{EraseRegion, EraseRegionsProvider};
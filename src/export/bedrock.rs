@@ -0,0 +1,364 @@
+//! Converts the player model with its Ears features into Bedrock Edition geometry.
+//!
+//! The player's base and overlay layers are the usual humanoid bones and cubes. Every feature
+//! element, like `ear_left` or `tail_segment_2`, is its own bone parented to the body part it is
+//! attached to, made of flat cubes. Bedrock can't animate these like Ears does, so features are
+//! exported in their rest pose and the differences are listed in
+//! [`BedrockModel::approximations`].
+
+use std::fmt::{self, Display};
+
+use image::RgbaImage;
+use image::imageops::{self, FilterType};
+
+use crate::export::common::{ElementQuads, group_elements, skin_textures};
+use crate::export::cubes::{PlaneCube, bounds};
+use crate::export::json::{Json, json_object, numbers};
+use crate::features::data::leg::LegMode;
+use crate::features::data::tail::TailMode;
+use crate::features::data::wing::{WingAnimationMode, WingMode};
+use crate::geometry::player::part_layouts;
+use crate::geometry::{BodyPart, ModelElement, Quad, QuadTexture, model_geometry};
+use crate::layout::WING_SIZE;
+use crate::utils::ProcessedSkin;
+
+/// The identifier of the exported geometry.
+pub const GEOMETRY_IDENTIFIER: &str = "geometry.ears.player";
+
+/// A Bedrock model of a player and the texture it uses.
+#[derive(Debug, Clone)]
+pub struct BedrockModel {
+    /// The `geometry.json` file.
+    pub geometry: String,
+    /// The skin, followed by the wing texture on its right when there is one.
+    pub texture: RgbaImage,
+    /// What couldn't be converted exactly.
+    pub approximations: Vec<Approximation>,
+}
+
+/// Something Ears renders that Bedrock geometry can't show the same way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Approximation {
+    /// The tail sways in Ears, but is exported in its rest pose.
+    StaticTail,
+    /// The wings flap in Ears, but are exported in their rest pose.
+    StaticWings,
+    /// The wings are enabled, but the skin has no wing texture, so they are left out.
+    MissingWings,
+    /// The wing texture was resized to fit the atlas.
+    ResizedWings,
+    /// Emissive pixels are drawn lit, like the rest of the skin.
+    Emissive,
+}
+
+impl Display for Approximation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Approximation::StaticTail => "the tail is not animated",
+            Approximation::StaticWings => "the wings are not animated",
+            Approximation::MissingWings => "the wings were left out as there is no wing texture",
+            Approximation::ResizedWings => "the wing texture was resized",
+            Approximation::Emissive => "emissive pixels are not drawn unlit",
+        })
+    }
+}
+
+/// Converts a player wearing `skin` into Bedrock geometry and its texture.
+pub fn write_bedrock(skin: &ProcessedSkin) -> BedrockModel {
+    let features = skin.features.unwrap_or_default();
+    let wings = skin.wing.as_ref();
+    let (base, _) = skin_textures(skin);
+
+    let mut approximations = Vec::new();
+    if features
+        .tail
+        .is_some_and(|tail| tail.animate && tail.mode != TailMode::None)
+    {
+        approximations.push(Approximation::StaticTail);
+    }
+    let has_wings = features.wing.filter(|wing| wing.mode != WingMode::None);
+    match (has_wings, wings) {
+        (Some(wing), Some(_)) if wing.animation_mode != WingAnimationMode::None => {
+            approximations.push(Approximation::StaticWings)
+        }
+        (Some(_), None) => approximations.push(Approximation::MissingWings),
+        _ => {}
+    }
+    if features.emissive {
+        approximations.push(Approximation::Emissive);
+    }
+
+    let scale = (base.width() / 64).max(1);
+    let (texture, texture_width) = match wings {
+        Some(wings) => {
+            let (width, height) = (WING_SIZE.0 * scale, WING_SIZE.1 * scale);
+            if wings.dimensions() != (width, height) {
+                approximations.push(Approximation::ResizedWings);
+            }
+
+            let mut atlas = RgbaImage::new(128 * scale, 64 * scale);
+            imageops::overlay(&mut atlas, &base, 0, 0);
+            let wings = imageops::resize(wings, width, height, FilterType::Nearest);
+            imageops::overlay(&mut atlas, &wings, 64 * i64::from(scale), 0);
+            (atlas, 128)
+        }
+        None => (base, 64),
+    };
+
+    let model = skin.model;
+    let quads = model_geometry(&features, model);
+    let mut bones = vec![
+        json_object! { "name" => "root", "pivot" => [0, 0, 0] },
+        json_object! { "name" => "waist", "parent" => "root", "pivot" => [0, 12, 0] },
+    ];
+
+    for layout in &part_layouts(model) {
+        let part = layout.part;
+        let (base, overlay) = bone_names(part);
+        let parent = match part {
+            BodyPart::Torso => "waist",
+            BodyPart::LeftLeg | BodyPart::RightLeg => "root",
+            _ => "body",
+        };
+        let bent = matches!(part, BodyPart::LeftLeg | BodyPart::RightLeg)
+            && matches!(
+                features.leg_mode,
+                LegMode::DigitigradePartial | LegMode::DigitigradeFull
+            );
+
+        // Boxes are placed where the base layer is, the overlay growing around it
+        let (min, max) = bounds(
+            quads
+                .iter()
+                .filter(|q| q.part == part && q.element == ModelElement::Base),
+        );
+
+        let layers = [
+            (ModelElement::Base, base, parent, layout.base, 0.0),
+            (
+                ModelElement::Overlay,
+                overlay,
+                base,
+                layout.overlay,
+                layout.inflate,
+            ),
+        ];
+        for (element, name, parent, (u, v), inflate) in layers {
+            let cubes = if bent {
+                quads
+                    .iter()
+                    .filter(|q| q.part == part && q.element == element)
+                    .filter_map(plane_cube)
+                    .collect()
+            } else {
+                let mut cube = json_object! {
+                    "origin" => numbers([min[0], min[1], -max[2]]),
+                    "size" => numbers(layout.size),
+                    "uv" => numbers([u, v]),
+                };
+                if inflate > 0.0 {
+                    cube.insert("inflate", inflate);
+                }
+                vec![cube]
+            };
+
+            bones.push(json_object! {
+                "name" => name,
+                "parent" => parent,
+                "pivot" => numbers(to_bedrock(part.pivot())),
+                "cubes" => cubes,
+            });
+        }
+    }
+
+    // One bone per feature element, in the order they were built
+    let feature_quads = quads
+        .iter()
+        .copied()
+        .filter(|q| q.element.is_feature())
+        .filter(|q| q.texture != QuadTexture::Wing || wings.is_some());
+    for ElementQuads {
+        part,
+        element,
+        quads,
+        ..
+    } in group_elements(feature_quads)
+    {
+        let cubes: Vec<Json> = quads.iter().filter_map(plane_cube).collect();
+        if cubes.is_empty() {
+            continue;
+        }
+        bones.push(json_object! {
+            "name" => element.name(part),
+            "parent" => bone_names(part).0,
+            "pivot" => numbers(to_bedrock(part.pivot())),
+            "cubes" => cubes,
+        });
+    }
+
+    let geometry = json_object! {
+        "format_version" => "1.16.0",
+        "minecraft:geometry" => vec![json_object! {
+            "description" => json_object! {
+                "identifier" => GEOMETRY_IDENTIFIER,
+                "texture_width" => texture_width,
+                "texture_height" => 64,
+                "visible_bounds_width" => 4,
+                "visible_bounds_height" => 4,
+                "visible_bounds_offset" => [0.0, 1.5, 0.0],
+            },
+            "bones" => bones,
+        }],
+    };
+
+    BedrockModel {
+        geometry: geometry.to_string(),
+        texture,
+        approximations,
+    }
+}
+
+/// The names of the Bedrock bones of the base and overlay layers of `part`.
+pub(crate) fn bone_names(part: BodyPart) -> (&'static str, &'static str) {
+    match part {
+        BodyPart::Head => ("head", "hat"),
+        BodyPart::Torso => ("body", "jacket"),
+        BodyPart::LeftArm => ("leftArm", "leftSleeve"),
+        BodyPart::RightArm => ("rightArm", "rightSleeve"),
+        BodyPart::LeftLeg => ("leftLeg", "leftPants"),
+        BodyPart::RightLeg => ("rightLeg", "rightPants"),
+    }
+}
+
+/// Bedrock geometry mirrors the `X` axis of Blockbench's space, so the player faces `-Z` with
+/// `+X` on the player's left.
+fn to_bedrock([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [x, y, -z]
+}
+
+/// A Bedrock cube drawing `quad`, with a single textured face.
+fn plane_cube(quad: &Quad) -> Option<Json> {
+    let cube = PlaneCube::from_quad(quad, |[u, v]| match quad.texture {
+        QuadTexture::Skin | QuadTexture::Displaced => [u * 64.0, v * 64.0],
+        QuadTexture::Wing => [64.0 + u * WING_SIZE.0 as f32, v * WING_SIZE.1 as f32],
+    })?;
+    let [x, y, z] = cube.from;
+    let [rx, ry, rz] = cube.rotation;
+    let [u1, v1, u2, v2] = cube.uv;
+
+    let mut json = json_object! {
+        "origin" => numbers([-(x + cube.width), y, z]),
+        "size" => numbers([cube.width, cube.height, 0.0]),
+    };
+    if cube.rotation != [0.0; 3] {
+        json.insert("pivot", numbers([-x, y, z]));
+        json.insert("rotation", numbers([-rx, -ry, rz]));
+    }
+    json.insert(
+        "uv",
+        json_object! {
+            "south" => json_object! {
+                "uv" => numbers([u1, v1]),
+                "uv_size" => numbers([u2 - u1, v2 - v1]),
+            },
+        },
+    );
+    Some(json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::errors::Result;
+
+    #[test]
+    fn writes_humanoid_bones_with_features() -> Result<()> {
+        let skin = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let mut skin = ProcessedSkin::from_image(&skin)?;
+        skin.wing = Some(RgbaImage::new(20, 16));
+
+        let model = write_bedrock(&skin);
+
+        assert_eq!(model.texture.dimensions(), (128, 64));
+        assert_eq!(
+            model.approximations,
+            [Approximation::StaticTail, Approximation::StaticWings]
+        );
+        assert!(model.geometry.contains(r#""texture_width":128"#));
+        assert!(model.geometry.contains(
+            r#"{"name":"head","parent":"body","pivot":[0,24,0],"cubes":[{"origin":[-4,24,-4],"size":[8,8,8],"uv":[0,0]}]}"#
+        ));
+        assert!(model.geometry.contains(
+            r#"{"name":"leftArm","parent":"body","pivot":[5,22,0],"cubes":[{"origin":[4,12,-2],"size":[4,12,4],"uv":[32,48]}]}"#
+        ));
+        for name in [
+            r#""name":"ear_left","parent":"head""#,
+            r#""name":"tail_segment_3","parent":"body""#,
+            r#""name":"snout","parent":"head""#,
+            r#""name":"wing_left","parent":"body""#,
+        ] {
+            assert!(model.geometry.contains(name), "missing {name}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn declares_a_format_with_per_face_uv() -> Result<()> {
+        let skin = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let model = write_bedrock(&ProcessedSkin::from_image(&skin)?);
+
+        // Per-face UV needs 1.16.0 or later
+        assert!(model.geometry.contains(r#""uv":{"south":{"uv":"#));
+        assert!(model.geometry.starts_with(r#"{"format_version":"1.16.0","#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_left_out_wings() -> Result<()> {
+        let skin = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let mut skin = ProcessedSkin::from_image(&skin)?;
+        skin.wing = None;
+
+        let model = write_bedrock(&skin);
+
+        assert_eq!(model.texture, skin.base);
+        assert!(model.approximations.contains(&Approximation::MissingWings));
+        assert!(!model.geometry.contains("wing_left"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn draws_displaced_legs_into_the_texture() -> Result<()> {
+        let skin = image::open("test_images/ears_v1_digitigrade_full_original.png")
+            .unwrap()
+            .to_rgba8();
+        let skin = ProcessedSkin::from_image(&skin)?;
+
+        let model = write_bedrock(&skin);
+
+        // The bent legs read the pixels that were erased from the base skin
+        assert_eq!(skin.base.get_pixel(4, 20)[3], 0);
+        assert_eq!(
+            model.texture.get_pixel(4, 20),
+            skin.displaced.unwrap().get_pixel(4, 20)
+        );
+        assert_eq!(model.texture.get_pixel(4, 20)[3], u8::MAX);
+
+        Ok(())
+    }
+}
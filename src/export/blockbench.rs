@@ -0,0 +1,321 @@
+//! Writes the player model with its Ears features as a Blockbench project (`.bbmodel`).
+//!
+//! The project uses Blockbench's Bedrock model format. Every body part is a group at its pivot,
+//! holding the cuboids of its base and overlay layers and one group per feature element, like
+//! `ear_left` or `tail_segment_2`, made of flat cubes. The skin, and the wing texture when there
+//! is one, are embedded in the project.
+
+use std::io::Cursor;
+
+use image::{ImageFormat, RgbaImage};
+
+use crate::export::bedrock::bone_names;
+use crate::export::common::{ElementQuads, group_elements, skin_textures};
+use crate::export::cubes::{PlaneCube, bounds, to_blockbench};
+use crate::export::json::{Json, json_object, numbers};
+use crate::features::data::leg::LegMode;
+use crate::geometry::player::part_layouts;
+use crate::geometry::{BodyPart, ModelElement, Quad, QuadTexture, model_geometry};
+use crate::utils::ProcessedSkin;
+use crate::utils::errors::Result;
+
+/// The project's UV resolution. Every texture is stretched over it.
+const RESOLUTION: f32 = 64.0;
+
+const FACES: [&str; 6] = ["north", "east", "south", "west", "up", "down"];
+
+/// Writes a player wearing `skin` as a `.bbmodel` file. Wings are left out when the skin has no
+/// wing texture.
+pub fn write_bbmodel(skin: &ProcessedSkin) -> Result<String> {
+    let mut project = Project::default();
+    let features = skin.features.unwrap_or_default();
+    let wings = skin.wing.as_ref();
+    let (base, _) = skin_textures(skin);
+
+    let mut textures = vec![project.texture("skin", 0, &base)?];
+    if let Some(wings) = wings {
+        textures.push(project.texture("wings", 1, wings)?);
+    }
+
+    let model = skin.model;
+    let quads = model_geometry(&features, model);
+    let mut groups = Vec::new();
+    for layout in &part_layouts(model) {
+        let part = layout.part;
+        let (base, overlay) = bone_names(part);
+        let bent = matches!(part, BodyPart::LeftLeg | BodyPart::RightLeg)
+            && matches!(
+                features.leg_mode,
+                LegMode::DigitigradePartial | LegMode::DigitigradeFull
+            );
+        let (min, max) = bounds(
+            quads
+                .iter()
+                .filter(|q| q.part == part && q.element == ModelElement::Base),
+        );
+
+        let mut children = Vec::new();
+        let layers = [
+            (ModelElement::Base, base, layout.base, 0.0),
+            (
+                ModelElement::Overlay,
+                overlay,
+                layout.overlay,
+                layout.inflate,
+            ),
+        ];
+        for (element, name, (u, v), inflate) in layers {
+            if bent {
+                for quad in quads
+                    .iter()
+                    .filter(|q| q.part == part && q.element == element)
+                {
+                    children.extend(project.plane(name, quad).map(Json::from));
+                }
+            } else {
+                let uuid = project.box_cube(name, (min, max), (u, v), inflate, part);
+                children.push(Json::from(uuid));
+            }
+        }
+
+        // One group per feature element, in the order they were built
+        let feature_quads = quads
+            .iter()
+            .copied()
+            .filter(|q| q.part == part && q.element.is_feature())
+            .filter(|q| q.texture != QuadTexture::Wing || wings.is_some());
+        for ElementQuads { element, quads, .. } in group_elements(feature_quads) {
+            let name = element.name(part);
+            let cubes: Vec<Json> = quads
+                .iter()
+                .filter_map(|quad| project.plane(&name, quad))
+                .map(Json::from)
+                .collect();
+            if !cubes.is_empty() {
+                children.push(project.group(&name, part, cubes));
+            }
+        }
+
+        groups.push(project.group(base, part, children));
+    }
+
+    let json = json_object! {
+        "meta" => json_object! {
+            "format_version" => "4.5",
+            "model_format" => "bedrock",
+            "box_uv" => false,
+        },
+        "name" => "ears_player",
+        "model_identifier" => "ears_player",
+        "visible_box" => [4.0, 4.0, 1.5],
+        "resolution" => json_object! { "width" => RESOLUTION, "height" => RESOLUTION },
+        "elements" => project.elements,
+        "outliner" => groups,
+        "textures" => textures,
+    };
+
+    Ok(json.to_string())
+}
+
+#[derive(Default)]
+struct Project {
+    elements: Vec<Json>,
+    uuids: u64,
+}
+
+impl Project {
+    /// Blockbench needs every element, group and texture to have a UUID, but doesn't check that
+    /// they are random.
+    fn uuid(&mut self) -> String {
+        self.uuids += 1;
+        format!("00000000-0000-4000-8000-{:012x}", self.uuids)
+    }
+
+    fn texture(&mut self, name: &str, id: u32, image: &RgbaImage) -> Result<Json> {
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+
+        Ok(json_object! {
+            "name" => format!("{name}.png"),
+            "id" => id.to_string(),
+            "uuid" => self.uuid(),
+            "path" => "",
+            "folder" => "",
+            "namespace" => "",
+            "width" => image.width(),
+            "height" => image.height(),
+            "uv_width" => RESOLUTION,
+            "uv_height" => RESOLUTION,
+            "particle" => false,
+            "mode" => "bitmap",
+            "saved" => false,
+            "source" => format!("data:image/png;base64,{}", base64(&png)),
+        })
+    }
+
+    fn group(&mut self, name: &str, part: BodyPart, children: Vec<Json>) -> Json {
+        json_object! {
+            "name" => name,
+            "origin" => numbers(to_blockbench(part.pivot())),
+            "uuid" => self.uuid(),
+            "export" => true,
+            "isOpen" => false,
+            "children" => children,
+        }
+    }
+
+    /// Adds a cuboid with the vanilla texture layout starting at (`u`, `v`), placed at `bounds`
+    /// in model space and grown by `inflate`.
+    fn box_cube(
+        &mut self,
+        name: &str,
+        (min, max): ([f32; 3], [f32; 3]),
+        (u, v): (f32, f32),
+        inflate: f32,
+        part: BodyPart,
+    ) -> String {
+        let from = to_blockbench([max[0], min[1], max[2]]);
+        let to = to_blockbench([min[0], max[1], min[2]]);
+        let [width, height, depth] = [0, 1, 2].map(|i| to[i] - from[i]);
+
+        // Blockbench's box layout, with its east side being the player's right
+        let uvs = [
+            [u + depth, v + depth, u + depth + width, v + depth + height],
+            [u, v + depth, u + depth, v + depth + height],
+            [
+                u + 2.0 * depth + width,
+                v + depth,
+                u + 2.0 * (depth + width),
+                v + depth + height,
+            ],
+            [
+                u + depth + width,
+                v + depth,
+                u + 2.0 * depth + width,
+                v + depth + height,
+            ],
+            [u + depth + width, v + depth, u + depth, v],
+            [u + depth + 2.0 * width, v, u + depth + width, v + depth],
+        ];
+        let mut faces = Json::Object(Vec::new());
+        for (face, uv) in FACES.iter().zip(uvs) {
+            faces.insert(face, json_object! { "uv" => numbers(uv), "texture" => 0 });
+        }
+
+        let uuid = self.uuid();
+        self.elements.push(json_object! {
+            "name" => name,
+            "type" => "cube",
+            "uuid" => uuid.as_str(),
+            "box_uv" => true,
+            "uv_offset" => numbers([u, v]),
+            "from" => numbers(from),
+            "to" => numbers(to),
+            "inflate" => inflate,
+            "origin" => numbers(to_blockbench(part.pivot())),
+            "faces" => faces,
+        });
+        uuid
+    }
+
+    /// Adds a flat cube drawing `quad`, if it isn't too small to have a direction.
+    fn plane(&mut self, name: &str, quad: &Quad) -> Option<String> {
+        let texture = match quad.texture {
+            QuadTexture::Skin | QuadTexture::Displaced => 0,
+            QuadTexture::Wing => 1,
+        };
+        let cube = PlaneCube::from_quad(quad, |uv| uv.map(|c| c * RESOLUTION))?;
+
+        let mut faces = Json::Object(Vec::new());
+        for face in FACES {
+            faces.insert(
+                face,
+                match face {
+                    "south" => json_object! { "uv" => numbers(cube.uv), "texture" => texture },
+                    _ => json_object! { "uv" => [0, 0, 0, 0], "texture" => Json::Null },
+                },
+            );
+        }
+
+        let [x, y, z] = cube.from;
+        let uuid = self.uuid();
+        self.elements.push(json_object! {
+            "name" => name,
+            "type" => "cube",
+            "uuid" => uuid.as_str(),
+            "box_uv" => false,
+            "from" => numbers(cube.from),
+            "to" => numbers([x + cube.width, y + cube.height, z]),
+            "origin" => numbers(cube.from),
+            "rotation" => numbers(cube.rotation),
+            "faces" => faces,
+        });
+        Some(uuid)
+    }
+}
+
+/// Encodes `bytes` as standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| chunk.get(i).copied().unwrap_or(0) as u32);
+        let triple = (a << 16) | (b << 8) | c;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(triple >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn writes_groups_and_named_features() -> Result<()> {
+        let skin = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let mut skin = ProcessedSkin::from_image(&skin)?;
+        skin.wing = Some(RgbaImage::new(20, 16));
+
+        let bbmodel = write_bbmodel(&skin)?;
+
+        assert!(bbmodel.contains(r#""model_format":"bedrock""#));
+        assert!(bbmodel.contains(r#""name":"skin.png""#));
+        assert!(bbmodel.contains(r#""name":"wings.png""#));
+        assert!(bbmodel.contains("data:image/png;base64,iVBORw0KGgo"));
+        assert!(bbmodel.contains(
+            r#""name":"head","type":"cube","uuid":"00000000-0000-4000-8000-000000000003","box_uv":true,"uv_offset":[0,0],"from":[-4,24,-4],"to":[4,32,4]"#
+        ));
+        for group in [
+            r#"{"name":"head","origin":[0,24,0]"#,
+            r#"{"name":"leftArm","origin":[-5,22,0]"#,
+            r#"{"name":"ear_left","origin":[0,24,0]"#,
+            r#"{"name":"tail_segment_2","origin":[0,24,0]"#,
+            r#"{"name":"wing_right","origin":[0,24,0]"#,
+        ] {
+            assert!(bbmodel.contains(group), "missing {group}");
+        }
+
+        Ok(())
+    }
+}
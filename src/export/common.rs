@@ -0,0 +1,79 @@
+use image::RgbaImage;
+
+use crate::geometry::{BodyPart, ModelElement, Quad, QuadTexture};
+use crate::utils::{ProcessedSkin, displaced_leg_regions};
+
+/// How many skin pixels make a meter, one block being 16 pixels.
+#[cfg(any(feature = "gltf", feature = "obj"))]
+pub(crate) const PIXELS_PER_METER: f32 = 16.0;
+
+/// The quads of one feature element or body part layer, and the texture they are exported with.
+pub(crate) struct ElementQuads {
+    pub(crate) part: BodyPart,
+    pub(crate) element: ModelElement,
+    pub(crate) texture: QuadTexture,
+    pub(crate) quads: Vec<Quad>,
+}
+
+/// Groups `quads` by body part, element and exported texture, in the order they were built.
+pub(crate) fn group_elements(quads: impl IntoIterator<Item = Quad>) -> Vec<ElementQuads> {
+    let mut elements: Vec<ElementQuads> = Vec::new();
+    for quad in quads {
+        let texture = exported_texture(quad.texture);
+        match elements
+            .iter_mut()
+            .find(|e| (e.part, e.element, e.texture) == (quad.part, quad.element, texture))
+        {
+            Some(element) => element.quads.push(quad),
+            None => elements.push(ElementQuads {
+                part: quad.part,
+                element: quad.element,
+                texture,
+                quads: vec![quad],
+            }),
+        }
+    }
+    elements
+}
+
+/// The texture quads drawn with `texture` are exported with. Displaced legs are drawn into the
+/// skin texture by [`skin_textures`].
+pub(crate) fn exported_texture(texture: QuadTexture) -> QuadTexture {
+    match texture {
+        QuadTexture::Displaced => QuadTexture::Skin,
+        texture => texture,
+    }
+}
+
+/// The skin texture of `skin` and its emissive texture, if any, with the displaced legs drawn
+/// back where they were erased from. Model files can't switch textures within a body part, and
+/// the displaced texture is laid out like the skin, so both fit in one.
+pub(crate) fn skin_textures(skin: &ProcessedSkin) -> (RgbaImage, Option<RgbaImage>) {
+    let regions = skin
+        .features
+        .map(|features| displaced_leg_regions(features.leg_mode))
+        .unwrap_or_default();
+    let scale = (skin.base.width() / 64).max(1);
+
+    let merge = |texture: &RgbaImage, displaced: Option<&RgbaImage>| {
+        let mut texture = texture.clone();
+        if let Some(displaced) = displaced {
+            for (x, y) in regions
+                .iter()
+                .flat_map(|(region, _)| region.scaled(scale).pixels())
+            {
+                if let Some(pixel) = displaced.get_pixel_checked(x, y) {
+                    texture.put_pixel(x, y, *pixel);
+                }
+            }
+        }
+        texture
+    };
+
+    let base = merge(&skin.base, skin.displaced.as_ref());
+    let emissive = skin
+        .emissive
+        .as_ref()
+        .map(|emissive| merge(emissive, skin.displaced_emissive.as_ref()));
+    (base, emissive)
+}
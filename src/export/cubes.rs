@@ -0,0 +1,167 @@
+use crate::geometry::Quad;
+use crate::geometry::math::{cross, dot, normalize, sub};
+
+/// A quad turned into a flat, rotated cube showing only its south face, the way cube-based
+/// formats like Bedrock geometry and Blockbench draw planes.
+///
+/// Positions are in Blockbench's space, where the player faces `-Z` and `+X` is the player's
+/// right: model space turned around the `Y` axis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct PlaneCube {
+    /// The bottom-left corner of the face, which the cube is also rotated around.
+    pub(crate) from: [f32; 3],
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    /// Degrees around the `X`, `Y` and `Z` axes, applied in that order.
+    pub(crate) rotation: [f32; 3],
+    /// The texels of the top-left and bottom-right corners of the face.
+    pub(crate) uv: [f32; 4],
+}
+
+/// Moves a model space position into Blockbench's space.
+pub(crate) fn to_blockbench([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [-x, y, -z]
+}
+
+impl PlaneCube {
+    /// Turns `quad` into a cube, `texels` mapping its UVs to texture coordinates. Quads too small
+    /// to have a direction are skipped.
+    pub(crate) fn from_quad(quad: &Quad, texels: impl Fn([f32; 2]) -> [f32; 2]) -> Option<Self> {
+        const EPSILON: f32 = 1e-4;
+
+        // The vertices go counter-clockwise from the top-left corner. Start from the one the
+        // texture's top-left corner is on, as cube faces can't rotate their texture.
+        let vertices = quad
+            .vertices
+            .map(|v| (to_blockbench(v.position), texels(v.uv)));
+        let [tl, bl, br, tr] = (0..4)
+            .map(|shift| {
+                let mut vertices = vertices;
+                vertices.rotate_left(shift);
+                vertices
+            })
+            .find(|[tl, bl, _, tr]| {
+                (tl.1[1] - tr.1[1]).abs() < EPSILON && (tl.1[0] - bl.1[0]).abs() < EPSILON
+            })?;
+
+        let right = sub(tr.0, tl.0);
+        let up = sub(tl.0, bl.0);
+        let (width, height) = (dot(right, right).sqrt(), dot(up, up).sqrt());
+        if width < EPSILON || height < EPSILON {
+            return None;
+        }
+
+        let x = normalize(right);
+        let y = normalize(up);
+        let z = cross(x, y);
+
+        Some(Self {
+            from: bl.0,
+            width,
+            height,
+            rotation: euler_xyz([x, y, z]),
+            uv: [tl.1[0], tl.1[1], br.1[0], br.1[1]],
+        })
+    }
+}
+
+/// The corners of the box around `quads`, in model space.
+pub(crate) fn bounds<'a>(quads: impl Iterator<Item = &'a Quad>) -> ([f32; 3], [f32; 3]) {
+    let mut min = [f32::MAX; 3];
+    let mut max = [f32::MIN; 3];
+    for position in quads.flat_map(|q| q.vertices.map(|v| v.position)) {
+        for axis in 0..3 {
+            min[axis] = min[axis].min(position[axis]);
+            max[axis] = max[axis].max(position[axis]);
+        }
+    }
+    (min, max)
+}
+
+/// Splits the rotation with the columns `x`, `y` and `z` into rotations around the `X`, `Y` and
+/// `Z` axes, applied in that order.
+fn euler_xyz([x, y, z]: [[f32; 3]; 3]) -> [f32; 3] {
+    let sin_y = (-x[2]).clamp(-1.0, 1.0);
+    let (rx, ry, rz) = if sin_y.abs() < 0.99999 {
+        (y[2].atan2(z[2]), sin_y.asin(), x[1].atan2(x[0]))
+    } else {
+        ((-z[1]).atan2(y[1]), sin_y.asin(), 0.0)
+    };
+
+    [rx, ry, rz].map(|angle| {
+        let degrees = angle.to_degrees();
+        if degrees.abs() < 1e-3 { 0.0 } else { degrees }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::EarsFeatures;
+    use crate::features::data::ear::EarMode;
+    use crate::features::data::leg::LegMode;
+    use crate::features::data::protrusions::Protrusions;
+    use crate::features::data::snout::SnoutData;
+    use crate::features::data::tail::{TailData, TailMode};
+    use crate::geometry::math::Mat4;
+    use crate::geometry::model_geometry;
+    use crate::utils::SkinModel;
+
+    #[test]
+    fn cubes_cover_their_quads() {
+        let features = EarsFeatures {
+            ear_mode: EarMode::Around,
+            tail: Some(TailData {
+                mode: TailMode::Star,
+                segments: 3,
+                bends: [10.0, 20.0, -30.0, 0.0],
+                ..Default::default()
+            }),
+            snout: Some(SnoutData {
+                offset: 1,
+                width: 4,
+                height: 2,
+                depth: 3,
+            }),
+            protrusions: Protrusions::ClawsAndHorn,
+            leg_mode: LegMode::DigitigradeFull,
+            chest_size: 0.5,
+            ..Default::default()
+        };
+
+        for quad in model_geometry(&features, SkinModel::Slim) {
+            let cube = PlaneCube::from_quad(&quad, |[u, v]| [u * 64.0, v * 64.0]).unwrap();
+            let [rx, ry, rz] = cube.rotation;
+            let rotation = Mat4::rotation(rz, [0.0, 0.0, 1.0])
+                * Mat4::rotation(ry, [0.0, 1.0, 0.0])
+                * Mat4::rotation(rx, [1.0, 0.0, 0.0]);
+
+            let corner = |x: f32, y: f32| {
+                let offset = rotation.transform_vector([x, y, 0.0]);
+                [0, 1, 2].map(|i| cube.from[i] + offset[i])
+            };
+            let [u1, v1, u2, v2] = cube.uv;
+            let corners = [
+                (corner(0.0, cube.height), [u1, v1]),
+                (corner(0.0, 0.0), [u1, v2]),
+                (corner(cube.width, 0.0), [u2, v2]),
+                (corner(cube.width, cube.height), [u2, v1]),
+            ];
+
+            for vertex in quad.vertices {
+                let position = to_blockbench(vertex.position);
+                let uv = vertex.uv.map(|c| c * 64.0);
+                assert!(corners.iter().any(|(p, t)| {
+                    sub(*p, position).iter().all(|d| d.abs() < 1e-3)
+                        && (t[0] - uv[0]).abs() < 1e-3
+                        && (t[1] - uv[1]).abs() < 1e-3
+                }));
+            }
+
+            // The south face of the cube faces the same way as the quad
+            let normal = rotation.transform_vector([0.0, 0.0, 1.0]);
+            let expected = to_blockbench(quad.normal);
+            assert!(sub(normal, expected).iter().all(|d| d.abs() < 1e-3));
+        }
+    }
+}
@@ -0,0 +1,414 @@
+//! Writes the player model with its Ears features as a binary glTF 2.0 (`.glb`) file.
+//!
+//! The player's base and overlay layers form a single mesh skinned to one bone per body part.
+//! Every feature element, like `ear_left` or `tail_segment_2`, is its own mesh, parented to the
+//! bone of the body part it is attached to. Positions are in meters, one block being 16 skin
+//! pixels.
+
+use std::io::Cursor;
+
+use image::{ImageFormat, RgbaImage};
+
+use crate::export::common::{ElementQuads, PIXELS_PER_METER, group_elements, skin_textures};
+use crate::export::json::{Json, json_object};
+use crate::geometry::{BodyPart, Quad, QuadTexture, model_geometry};
+use crate::utils::ProcessedSkin;
+use crate::utils::errors::Result;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+const UNSIGNED_BYTE: u32 = 5121;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+const NEAREST: u32 = 9728;
+
+/// Writes a player wearing `skin` as a `.glb` file, with the emissive pixels as the emissive
+/// map. Wings are left out when the skin has no wing texture.
+pub fn write_glb(skin: &ProcessedSkin) -> Result<Vec<u8>> {
+    let mut writer = GltfWriter::default();
+    let features = skin.features.unwrap_or_default();
+    let (base, emissive) = skin_textures(skin);
+
+    let skin_texture = writer.texture(&base)?;
+    let emissive_texture = emissive.as_ref().map(|e| writer.texture(e)).transpose()?;
+    let mut skin_material = json_object! {
+        "name" => "skin",
+        "pbrMetallicRoughness" => json_object! {
+            "baseColorTexture" => json_object! { "index" => skin_texture },
+            "metallicFactor" => 0,
+            "roughnessFactor" => 1,
+        },
+        "alphaMode" => "MASK",
+    };
+    if let Some(texture) = emissive_texture {
+        skin_material.insert("emissiveTexture", json_object! { "index" => texture });
+        skin_material.insert("emissiveFactor", [1, 1, 1]);
+    }
+    writer.materials.push(skin_material);
+
+    let wing_material = skin
+        .wing
+        .as_ref()
+        .map(|wings| -> Result<usize> {
+            let texture = writer.texture(wings)?;
+            writer.materials.push(json_object! {
+                "name" => "wings",
+                "pbrMetallicRoughness" => json_object! {
+                    "baseColorTexture" => json_object! { "index" => texture },
+                    "metallicFactor" => 0,
+                    "roughnessFactor" => 1,
+                },
+                "alphaMode" => "MASK",
+                "doubleSided" => true,
+            });
+            Ok(writer.materials.len() - 1)
+        })
+        .transpose()?;
+
+    let quads = model_geometry(&features, skin.model);
+    let (player, feature_quads): (Vec<Quad>, Vec<Quad>) =
+        quads.into_iter().partition(|q| !q.element.is_feature());
+
+    // The bones, one per body part, all children of the root node
+    let root = writer.nodes.len();
+    writer.nodes.push(Json::Null);
+    let bones: Vec<usize> = BodyPart::ALL
+        .iter()
+        .map(|part| {
+            writer.node(json_object! {
+                "name" => part.name(),
+                "translation" => to_meters(part.pivot()),
+            })
+        })
+        .collect();
+
+    let joints = BodyPart::ALL.map(|part| Some(bone_index(part) as u8));
+    let player_mesh = writer.mesh("player", &player, 0, [0.0; 3], &joints)?;
+    let player_node = writer.node(json_object! {
+        "name" => "player",
+        "mesh" => player_mesh,
+        "skin" => 0,
+    });
+
+    // One mesh per feature element, in the order they were built
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); bones.len()];
+    for ElementQuads {
+        part,
+        element,
+        texture,
+        quads,
+    } in group_elements(feature_quads)
+    {
+        let material = match texture {
+            QuadTexture::Skin | QuadTexture::Displaced => 0,
+            QuadTexture::Wing => match wing_material {
+                Some(material) => material,
+                None => continue,
+            },
+        };
+
+        let name = element.name(part);
+        let mesh = writer.mesh(&name, &quads, material, part.pivot(), &[None; 6])?;
+        let node = writer.node(json_object! { "name" => name, "mesh" => mesh });
+        children[bone_index(part)].push(node);
+    }
+    for (bone, children) in bones.iter().zip(children) {
+        if !children.is_empty() {
+            writer.nodes[*bone].insert("children", children);
+        }
+    }
+
+    writer.nodes[root] = json_object! {
+        "name" => "root",
+        "children" => bones.iter().copied().chain([player_node]).collect::<Vec<_>>(),
+    };
+
+    // The bones sit at their pivots, so binding only has to undo that translation
+    let inverse_binds: Vec<f32> = BodyPart::ALL
+        .iter()
+        .flat_map(|part| {
+            let [x, y, z] = to_meters(part.pivot());
+            [
+                1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, -x, -y, -z, 1.0,
+            ]
+        })
+        .collect();
+    let view = writer.view(&f32_bytes(&inverse_binds), None);
+    let inverse_binds = writer.accessor(view, FLOAT, BodyPart::ALL.len(), "MAT4", None);
+    let skins = vec![json_object! {
+        "name" => "player",
+        "skeleton" => root,
+        "joints" => bones,
+        "inverseBindMatrices" => inverse_binds,
+    }];
+
+    Ok(writer.finish(root, skins))
+}
+
+fn bone_index(part: BodyPart) -> usize {
+    BodyPart::ALL.iter().position(|p| *p == part).unwrap()
+}
+
+fn to_meters(position: [f32; 3]) -> [f32; 3] {
+    position.map(|c| c / PIXELS_PER_METER)
+}
+
+fn f32_bytes(values: &[f32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[derive(Default)]
+struct GltfWriter {
+    bin: Vec<u8>,
+    buffer_views: Vec<Json>,
+    accessors: Vec<Json>,
+    images: Vec<Json>,
+    textures: Vec<Json>,
+    materials: Vec<Json>,
+    meshes: Vec<Json>,
+    nodes: Vec<Json>,
+}
+
+impl GltfWriter {
+    fn view(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        let offset = self.bin.len();
+        self.bin.extend_from_slice(bytes);
+        self.bin.resize(self.bin.len().next_multiple_of(4), 0);
+
+        let mut view = json_object! {
+            "buffer" => 0,
+            "byteOffset" => offset,
+            "byteLength" => bytes.len(),
+        };
+        if let Some(target) = target {
+            view.insert("target", target);
+        }
+        self.buffer_views.push(view);
+        self.buffer_views.len() - 1
+    }
+
+    fn accessor(
+        &mut self,
+        view: usize,
+        component_type: u32,
+        count: usize,
+        kind: &str,
+        bounds: Option<([f32; 3], [f32; 3])>,
+    ) -> usize {
+        let mut accessor = json_object! {
+            "bufferView" => view,
+            "componentType" => component_type,
+            "count" => count,
+            "type" => kind,
+        };
+        if let Some((min, max)) = bounds {
+            accessor.insert("min", min);
+            accessor.insert("max", max);
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn texture(&mut self, image: &RgbaImage) -> Result<usize> {
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+
+        let view = self.view(&png, None);
+        self.images
+            .push(json_object! { "bufferView" => view, "mimeType" => "image/png" });
+        self.textures.push(json_object! {
+            "sampler" => 0,
+            "source" => self.images.len() - 1,
+        });
+        Ok(self.textures.len() - 1)
+    }
+
+    fn node(&mut self, node: Json) -> usize {
+        self.nodes.push(node);
+        self.nodes.len() - 1
+    }
+
+    /// Adds a mesh of `quads` with positions relative to `origin`, skinned to the bone of
+    /// `joints[part]` when there is one.
+    fn mesh(
+        &mut self,
+        name: &str,
+        quads: &[Quad],
+        material: usize,
+        origin: [f32; 3],
+        joints: &[Option<u8>; 6],
+    ) -> Result<usize> {
+        let mut positions = Vec::with_capacity(quads.len() * 12);
+        let mut normals = Vec::with_capacity(quads.len() * 12);
+        let mut uvs = Vec::with_capacity(quads.len() * 8);
+        let mut joint_indices = Vec::new();
+        let mut indices = Vec::with_capacity(quads.len() * 6);
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+
+        for (i, quad) in quads.iter().enumerate() {
+            for vertex in quad.vertices {
+                let position = to_meters([0, 1, 2].map(|a| vertex.position[a] - origin[a]));
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(position[axis]);
+                    max[axis] = max[axis].max(position[axis]);
+                }
+                positions.extend(position);
+                normals.extend(quad.normal);
+                uvs.extend(vertex.uv);
+                if let Some(joint) = joints[bone_index(quad.part)] {
+                    joint_indices.extend([joint, 0, 0, 0]);
+                }
+            }
+
+            let first = (i * 4) as u32;
+            indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+        }
+
+        let count = quads.len() * 4;
+        let view = self.view(&f32_bytes(&positions), Some(ARRAY_BUFFER));
+        let position = self.accessor(view, FLOAT, count, "VEC3", Some((min, max)));
+        let view = self.view(&f32_bytes(&normals), Some(ARRAY_BUFFER));
+        let normal = self.accessor(view, FLOAT, count, "VEC3", None);
+        let view = self.view(&f32_bytes(&uvs), Some(ARRAY_BUFFER));
+        let uv = self.accessor(view, FLOAT, count, "VEC2", None);
+
+        let mut attributes = json_object! {
+            "POSITION" => position,
+            "NORMAL" => normal,
+            "TEXCOORD_0" => uv,
+        };
+        if !joint_indices.is_empty() {
+            let view = self.view(&joint_indices, Some(ARRAY_BUFFER));
+            let joints = self.accessor(view, UNSIGNED_BYTE, count, "VEC4", None);
+            let weights = [1.0f32, 0.0, 0.0, 0.0].repeat(count);
+            let view = self.view(&f32_bytes(&weights), Some(ARRAY_BUFFER));
+            let weights = self.accessor(view, FLOAT, count, "VEC4", None);
+            attributes.insert("JOINTS_0", joints);
+            attributes.insert("WEIGHTS_0", weights);
+        }
+
+        let index_bytes: Vec<u8> = indices.iter().flat_map(|i| i.to_le_bytes()).collect();
+        let view = self.view(&index_bytes, Some(ELEMENT_ARRAY_BUFFER));
+        let indices = self.accessor(view, UNSIGNED_INT, indices.len(), "SCALAR", None);
+
+        self.meshes.push(json_object! {
+            "name" => name,
+            "primitives" => vec![json_object! {
+                "attributes" => attributes,
+                "indices" => indices,
+                "material" => material,
+            }],
+        });
+        Ok(self.meshes.len() - 1)
+    }
+
+    fn finish(self, root: usize, skins: Vec<Json>) -> Vec<u8> {
+        let json = json_object! {
+            "asset" => json_object! { "version" => "2.0", "generator" => "ears-rs" },
+            "scene" => 0,
+            "scenes" => vec![json_object! { "nodes" => [root] }],
+            "nodes" => self.nodes,
+            "meshes" => self.meshes,
+            "skins" => skins,
+            "materials" => self.materials,
+            "textures" => self.textures,
+            "images" => self.images,
+            "samplers" => vec![json_object! {
+                "magFilter" => NEAREST,
+                "minFilter" => NEAREST,
+            }],
+            "accessors" => self.accessors,
+            "bufferViews" => self.buffer_views,
+            "buffers" => vec![json_object! { "byteLength" => self.bin.len() }],
+        };
+
+        let mut json = json.to_string().into_bytes();
+        json.resize(json.len().next_multiple_of(4), b' ');
+
+        let length = 12 + 8 + json.len() + 8 + self.bin.len();
+        let mut glb = Vec::with_capacity(length);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(length as u32).to_le_bytes());
+
+        glb.extend_from_slice(&(json.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(&json);
+
+        glb.extend_from_slice(&(self.bin.len() as u32).to_le_bytes());
+        glb.extend_from_slice(b"BIN\0");
+        glb.extend_from_slice(&self.bin);
+
+        glb
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunks(glb: &[u8]) -> (String, &[u8]) {
+        assert_eq!(&glb[0..4], b"glTF");
+        assert_eq!(
+            u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+            glb.len()
+        );
+
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        assert_eq!(&glb[16..20], b"JSON");
+        let json = String::from_utf8(glb[20..20 + json_length].to_vec()).unwrap();
+
+        let bin = &glb[20 + json_length..];
+        assert_eq!(&bin[4..8], b"BIN\0");
+        (json, &bin[8..])
+    }
+
+    #[test]
+    fn writes_skinned_player_with_features() -> Result<()> {
+        let skin = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let mut skin = ProcessedSkin::from_image(&skin)?;
+        skin.wing = Some(RgbaImage::new(20, 16));
+
+        let glb = write_glb(&skin)?;
+        let (json, bin) = chunks(&glb);
+
+        assert!(json.starts_with('{') && json.trim_end().ends_with('}'));
+        assert!(json.contains(&format!(r#""buffers":[{{"byteLength":{}}}]"#, bin.len())));
+        for name in [
+            "\"ear_left\"",
+            "\"tail_segment_3\"",
+            "\"snout\"",
+            "\"wing_right\"",
+        ] {
+            assert!(json.contains(name), "{name} is missing");
+        }
+        assert!(json.contains(r#""JOINTS_0""#));
+        assert!(!json.contains("emissiveTexture"));
+
+        skin.wing = None;
+        let without_wings = write_glb(&skin)?;
+        assert!(!chunks(&without_wings).0.contains("wing_right"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn emissive_skins_have_an_emissive_map() -> Result<()> {
+        let skin = image::open("test_images/emissive-before.png")
+            .unwrap()
+            .to_rgba8();
+        let (json, _) = chunks(&write_glb(&ProcessedSkin::from_image(&skin)?)?);
+
+        assert!(json.contains(r#""emissiveTexture":{"index":1}"#));
+        assert!(json.contains(r#""images":[{"bufferView""#));
+
+        Ok(())
+    }
+}
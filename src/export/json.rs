@@ -0,0 +1,156 @@
+use std::fmt::{self, Display, Write};
+
+/// A minimal JSON document, written compactly by its [`Display`] implementation.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Adds `key` to this object.
+    ///
+    /// # Panics
+    /// If this isn't an object.
+    pub(crate) fn insert(&mut self, key: &str, value: impl Into<Json>) {
+        match self {
+            Json::Object(entries) => entries.push((key.to_string(), value.into())),
+            _ => panic!("cannot insert {key} into a non-object JSON value"),
+        }
+    }
+}
+
+/// Builds a [`Json::Object`] out of `key => value` pairs.
+macro_rules! json_object {
+    ($($key:expr => $value:expr),* $(,)?) => {
+        $crate::export::json::Json::Object(vec![
+            $(($key.to_string(), $crate::export::json::Json::from($value))),*
+        ])
+    };
+}
+
+pub(crate) use json_object;
+
+impl Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{value}"),
+            Json::Number(value) if value.is_finite() => write!(f, "{value}"),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(value) => write_string(f, value),
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(entries) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    write_string(f, key)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Rounds away float noise, so written files stay readable.
+pub(crate) fn numbers<const N: usize>(values: [f32; N]) -> [f64; N] {
+    values.map(|v| (f64::from(v) * 1e4).round() / 1e4 + 0.0)
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+macro_rules! json_number {
+    ($($ty:ty),*) => {
+        $(impl From<$ty> for Json {
+            fn from(value: $ty) -> Self {
+                Json::Number(value as f64)
+            }
+        })*
+    };
+}
+
+json_number!(f32, f64, u8, u32, i32, usize);
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Self {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Json>, const N: usize> From<[T; N]> for Json {
+    fn from(values: [T; N]) -> Self {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Json::Null, Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn writes_compact_json() {
+        let mut json = json_object! {
+            "name" => "ear \"left\"\n",
+            "values" => [1.5f32, 2.0, -0.25],
+            "empty" => Vec::<u32>::new(),
+        };
+        json.insert("missing", None::<bool>);
+
+        assert_eq!(
+            json.to_string(),
+            r#"{"name":"ear \"left\"\n","values":[1.5,2,-0.25],"empty":[],"missing":null}"#
+        );
+    }
+}
@@ -0,0 +1,12 @@
+//! Exporters writing the [geometry](crate::geometry) of a player to model files.
+
+pub mod bedrock;
+#[cfg(feature = "blockbench")]
+pub mod blockbench;
+mod common;
+mod cubes;
+#[cfg(feature = "gltf")]
+pub mod gltf;
+mod json;
+#[cfg(feature = "obj")]
+pub mod obj;
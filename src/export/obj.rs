@@ -0,0 +1,186 @@
+//! Writes the player model with its Ears features as a Wavefront OBJ file, with its MTL material
+//! library and textures.
+//!
+//! Every body part layer, like `head` or `hat`, and every feature element, like `ear_left` or
+//! `tail_segment_2`, is its own object. Positions are in meters, one block being 16 skin pixels,
+//! and numbers are rounded so that the output of the same model is always the same.
+
+use std::fmt::Write;
+use std::io::Cursor;
+
+use image::{ImageFormat, RgbaImage};
+
+use crate::export::common::{ElementQuads, PIXELS_PER_METER, group_elements, skin_textures};
+use crate::export::json::numbers;
+use crate::geometry::{ModelElement, QuadTexture, model_geometry};
+use crate::utils::ProcessedSkin;
+use crate::utils::errors::Result;
+
+pub const MTL_FILE: &str = "player.mtl";
+pub const SKIN_FILE: &str = "skin.png";
+pub const EMISSIVE_FILE: &str = "skin_emissive.png";
+pub const WINGS_FILE: &str = "wings.png";
+
+/// An OBJ model and the files it refers to.
+#[derive(Debug, Clone)]
+pub struct ObjModel {
+    pub obj: String,
+    /// The material library, to be saved as [`MTL_FILE`] next to the OBJ file.
+    pub mtl: String,
+    /// The PNG files used by the materials, with the names they are referred to by.
+    pub textures: Vec<(&'static str, Vec<u8>)>,
+}
+
+/// Writes a player wearing `skin` as an OBJ model, with the emissive pixels as the emissive map.
+/// Wings are left out when the skin has no wing texture.
+pub fn write_obj(skin: &ProcessedSkin) -> Result<ObjModel> {
+    let features = skin.features.unwrap_or_default();
+    let wings = skin.wing.as_ref();
+    let (base, emissive) = skin_textures(skin);
+
+    let mut mtl = format!("newmtl skin\nKd 1 1 1\nmap_Kd {SKIN_FILE}\n");
+    let mut textures = vec![(SKIN_FILE, png(&base)?)];
+    if let Some(emissive) = &emissive {
+        let _ = write!(mtl, "Ke 1 1 1\nmap_Ke {EMISSIVE_FILE}\n");
+        textures.push((EMISSIVE_FILE, png(emissive)?));
+    }
+    if let Some(wings) = wings {
+        let _ = write!(mtl, "\nnewmtl wings\nKd 1 1 1\nmap_Kd {WINGS_FILE}\n");
+        textures.push((WINGS_FILE, png(wings)?));
+    }
+
+    // One object per layer and feature element, in the order they were built
+    let quads = model_geometry(&features, skin.model)
+        .into_iter()
+        .filter(|q| q.texture != QuadTexture::Wing || wings.is_some());
+
+    let mut obj = format!("mtllib {MTL_FILE}\n");
+    let mut vertices = 0;
+    for ElementQuads {
+        part,
+        element,
+        texture,
+        quads,
+    } in group_elements(quads)
+    {
+        let name = match element {
+            ModelElement::Base => part.name().to_string(),
+            ModelElement::Overlay => part.overlay_name().to_string(),
+            _ => element.name(part),
+        };
+        let material = match texture {
+            QuadTexture::Skin | QuadTexture::Displaced => "skin",
+            QuadTexture::Wing => "wings",
+        };
+        let _ = write!(obj, "\no {name}\nusemtl {material}\n");
+
+        for quad in &quads {
+            for vertex in quad.vertices {
+                let [x, y, z] = numbers(vertex.position.map(|c| c / PIXELS_PER_METER));
+                let _ = writeln!(obj, "v {x} {y} {z}");
+            }
+            for vertex in quad.vertices {
+                let [u, v] = numbers([vertex.uv[0], 1.0 - vertex.uv[1]]);
+                let _ = writeln!(obj, "vt {u} {v}");
+            }
+            let [x, y, z] = numbers(quad.normal);
+            let _ = writeln!(obj, "vn {x} {y} {z}");
+        }
+        for (i, _) in quads.iter().enumerate() {
+            let normal = vertices / 4 + i + 1;
+            let corners = (1..=4).map(|corner| {
+                let index = vertices + i * 4 + corner;
+                format!("{index}/{index}/{normal}")
+            });
+            let _ = writeln!(obj, "f {}", corners.collect::<Vec<_>>().join(" "));
+        }
+        vertices += quads.len() * 4;
+    }
+
+    Ok(ObjModel { obj, mtl, textures })
+}
+
+fn png(image: &RgbaImage) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_an_object_per_part_and_feature() -> Result<()> {
+        let skin = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let mut skin = ProcessedSkin::from_image(&skin)?;
+        skin.wing = Some(RgbaImage::new(20, 16));
+
+        let model = write_obj(&skin)?;
+
+        for object in [
+            "o head\nusemtl skin\n",
+            "o hat\nusemtl skin\n",
+            "o left_pants\nusemtl skin\n",
+            "o ear_left\nusemtl skin\n",
+            "o tail_segment_3\nusemtl skin\n",
+            "o wing_right\nusemtl wings\n",
+        ] {
+            assert!(model.obj.contains(object), "missing {object}");
+        }
+        assert!(model.mtl.contains("map_Kd wings.png"));
+        assert!(!model.mtl.contains("map_Ke"));
+        assert_eq!(
+            model
+                .textures
+                .iter()
+                .map(|(name, _)| *name)
+                .collect::<Vec<_>>(),
+            [SKIN_FILE, WINGS_FILE]
+        );
+
+        // Every face refers to vertices that exist
+        let vertices = model.obj.lines().filter(|l| l.starts_with("v ")).count();
+        let last = model.obj.lines().rfind(|l| l.starts_with("f ")).unwrap();
+        assert!(last.ends_with(&format!(" {vertices}/{vertices}/{}", vertices / 4)));
+
+        // 12 layers and 11 feature elements, once the wings are left out
+        skin.wing = None;
+        assert_eq!(write_obj(&skin)?.obj.matches("\no ").count(), 23);
+
+        Ok(())
+    }
+
+    #[test]
+    fn writes_the_emissive_map() -> Result<()> {
+        let skin = image::open("test_images/emissive-before.png")
+            .unwrap()
+            .to_rgba8();
+        let model = write_obj(&ProcessedSkin::from_image(&skin)?)?;
+
+        assert!(model.mtl.contains("map_Ke skin_emissive.png"));
+        assert_eq!(model.textures[1].0, EMISSIVE_FILE);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bent_legs_are_one_object_per_layer() -> Result<()> {
+        let skin = image::open("test_images/ears_v1_digitigrade_partial_original.png")
+            .unwrap()
+            .to_rgba8();
+
+        let model = write_obj(&ProcessedSkin::from_image(&skin)?)?;
+
+        // The displaced shin shares the object and texture of the thigh
+        assert_eq!(model.obj.matches("\no left_leg\n").count(), 1);
+        assert_eq!(model.obj.matches("\no right_pants\n").count(), 1);
+        assert_eq!(model.textures.len(), 1);
+
+        Ok(())
+    }
+}
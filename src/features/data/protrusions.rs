@@ -12,3 +12,36 @@ pub enum Protrusions {
     ClawsAndHalo,
     ClawsAndDoubleHalo,
 }
+
+impl Protrusions {
+    pub fn has_claws(&self) -> bool {
+        matches!(
+            self,
+            Protrusions::Claws
+                | Protrusions::ClawsAndHorn
+                | Protrusions::ClawsAndHalo
+                | Protrusions::ClawsAndDoubleHalo
+        )
+    }
+
+    pub fn has_horn(&self) -> bool {
+        matches!(self, Protrusions::Horn | Protrusions::ClawsAndHorn)
+    }
+
+    pub fn has_halo(&self) -> bool {
+        matches!(
+            self,
+            Protrusions::Halo
+                | Protrusions::DoubleHalo
+                | Protrusions::ClawsAndHalo
+                | Protrusions::ClawsAndDoubleHalo
+        )
+    }
+
+    pub fn has_double_halo(&self) -> bool {
+        matches!(
+            self,
+            Protrusions::DoubleHalo | Protrusions::ClawsAndDoubleHalo
+        )
+    }
+}
@@ -1,4 +1,5 @@
 pub mod data;
+pub mod textures;
 use crate::features::data::ear::{EarAnchor, EarMode};
 use data::{
     leg::LegMode, protrusions::Protrusions, snout::SnoutData, tail::TailData, wing::WingData,
@@ -0,0 +1,632 @@
+//! Where Ears reads each feature's texture from in the skin.
+//!
+//! Regions are given in 64x64 skin coordinates and scaled up for HD skins. Textures are cropped
+//! as they are laid out on the skin, some of them are rotated when Ears renders them.
+//! The ear anchor only moves the ears, so it doesn't change which texels they use. Neither does
+//! the [`SkinModel`](crate::utils::SkinModel): arm claws are read from the corner next to the
+//! bottom of the arm, which slim arms leave unused too.
+//! Left and right are from the player's point of view.
+
+use std::collections::HashMap;
+
+use image::{RgbaImage, imageops};
+
+use crate::features::EarsFeatures;
+use crate::features::data::ear::EarMode;
+pub use crate::layout::TextureRegion;
+use crate::layout::skin_scale;
+use crate::utils::errors::{EarsError, Result};
+
+/// A single texture Ears reads for one of the features.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FeatureTexture {
+    EarFront,
+    EarBack,
+    LeftEarFront,
+    LeftEarBack,
+    RightEarFront,
+    RightEarBack,
+    LeftEarSideFront,
+    LeftEarSideBack,
+    RightEarSideFront,
+    RightEarSideBack,
+    Tail,
+    SnoutFront,
+    SnoutTop,
+    SnoutBottom,
+    SnoutLeft,
+    SnoutRight,
+    Horn,
+    Halo,
+    LeftArmClaw,
+    RightArmClaw,
+    LeftLegClaw,
+    RightLegClaw,
+}
+
+/// Where a [`FeatureTexture`] is stored in the skin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureRegion {
+    pub texture: FeatureTexture,
+    pub region: TextureRegion,
+    /// Whether the texture is stored upside down, like the tail when it is swapped with the
+    /// jacket back.
+    pub flipped: bool,
+}
+
+impl FeatureRegion {
+    const fn new(texture: FeatureTexture, x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            texture,
+            region: TextureRegion::new(x, y, width, height),
+            flipped: false,
+        }
+    }
+
+    fn crop(&self, image: &RgbaImage, scale: u32) -> RgbaImage {
+        let region = self.region.scaled(scale);
+        let texture =
+            imageops::crop_imm(image, region.x, region.y, region.width, region.height).to_image();
+
+        if self.flipped {
+            imageops::flip_vertical(&texture)
+        } else {
+            texture
+        }
+    }
+}
+
+/// How the ears of an [`EarMode`] are built.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EarLayout {
+    /// A single plane spanning the head.
+    Single,
+    /// A single plane spanning the head, plus a plane on each side of the head.
+    Around,
+    /// Separate left and right ears.
+    Pair,
+}
+
+fn ear_layout(mode: EarMode) -> Option<EarLayout> {
+    match mode {
+        EarMode::None => None,
+        EarMode::Above | EarMode::Behind | EarMode::Tall | EarMode::TallCross => {
+            Some(EarLayout::Single)
+        }
+        EarMode::Around => Some(EarLayout::Around),
+        EarMode::Sides | EarMode::Floppy | EarMode::Cross | EarMode::Out => Some(EarLayout::Pair),
+    }
+}
+
+/// Returns where Ears reads the texture of every feature enabled in `features`, in the skin as it
+/// is stored.
+pub fn feature_regions(features: &EarsFeatures) -> Vec<FeatureRegion> {
+    use FeatureTexture::*;
+
+    let mut regions = Vec::new();
+
+    match ear_layout(features.ear_mode) {
+        Some(EarLayout::Single) => regions.extend([
+            FeatureRegion::new(EarFront, 24, 0, 16, 8),
+            FeatureRegion::new(EarBack, 56, 28, 8, 16),
+        ]),
+        Some(EarLayout::Around) => regions.extend([
+            FeatureRegion::new(EarFront, 24, 0, 16, 8),
+            FeatureRegion::new(EarBack, 56, 28, 8, 16),
+            FeatureRegion::new(LeftEarSideFront, 36, 16, 8, 4),
+            FeatureRegion::new(LeftEarSideBack, 12, 16, 8, 4),
+            FeatureRegion::new(RightEarSideFront, 36, 32, 8, 4),
+            FeatureRegion::new(RightEarSideBack, 12, 32, 8, 4),
+        ]),
+        Some(EarLayout::Pair) => regions.extend([
+            FeatureRegion::new(LeftEarFront, 32, 0, 8, 8),
+            FeatureRegion::new(LeftEarBack, 56, 36, 8, 8),
+            FeatureRegion::new(RightEarFront, 24, 0, 8, 8),
+            FeatureRegion::new(RightEarBack, 56, 28, 8, 8),
+        ]),
+        None => {}
+    }
+
+    if let Some(tail) = features.tail {
+        regions.push(if tail.swap_jacket_back {
+            FeatureRegion {
+                flipped: true,
+                ..FeatureRegion::new(Tail, 32, 36, 8, 12)
+            }
+        } else {
+            FeatureRegion::new(Tail, 56, 16, 8, 12)
+        });
+    }
+
+    if let Some(snout) = features.snout {
+        let (width, height) = (snout.width as u32, snout.height as u32);
+        regions.extend([
+            FeatureRegion::new(SnoutFront, 0, 2, width, height),
+            FeatureRegion::new(SnoutTop, 0, 1, width, 1),
+            FeatureRegion::new(SnoutBottom, 0, 2 + height, width, 1),
+            FeatureRegion::new(SnoutLeft, 7, 0, 1, height),
+            FeatureRegion::new(SnoutRight, 7, 4, 1, height),
+        ]);
+    }
+
+    if features.protrusions.has_horn() {
+        regions.push(FeatureRegion::new(Horn, 56, 0, 8, 8));
+    }
+    if features.protrusions.has_halo() {
+        regions.push(FeatureRegion::new(Halo, 56, 0, 8, 8));
+    }
+    if features.protrusions.has_claws() {
+        regions.extend([
+            FeatureRegion::new(LeftArmClaw, 44, 48, 4, 4),
+            FeatureRegion::new(RightArmClaw, 52, 16, 4, 4),
+            FeatureRegion::new(LeftLegClaw, 16, 48, 4, 4),
+            FeatureRegion::new(RightLegClaw, 0, 16, 4, 4),
+        ]);
+    }
+
+    regions
+}
+
+/// Returns where the textures of every feature enabled in `features` are once the skin is
+/// processed. Processing swaps the tail back in place, so it is never read from the jacket back.
+pub(crate) fn processed_feature_regions(features: &EarsFeatures) -> Vec<FeatureRegion> {
+    let mut features = *features;
+    if let Some(tail) = &mut features.tail {
+        tail.swap_jacket_back = false;
+    }
+    feature_regions(&features)
+}
+
+/// The front and back texture of a flat feature plane.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlaneTextures {
+    pub front: RgbaImage,
+    pub back: RgbaImage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EarTextures {
+    /// Used by [`EarMode::Above`], [`EarMode::Behind`], [`EarMode::Tall`] and
+    /// [`EarMode::TallCross`].
+    Single(PlaneTextures),
+    /// Used by [`EarMode::Around`].
+    Around {
+        top: PlaneTextures,
+        left: PlaneTextures,
+        right: PlaneTextures,
+    },
+    /// Used by [`EarMode::Sides`], [`EarMode::Floppy`], [`EarMode::Cross`] and [`EarMode::Out`].
+    Pair {
+        left: PlaneTextures,
+        right: PlaneTextures,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnoutTextures {
+    pub front: RgbaImage,
+    pub top: RgbaImage,
+    pub bottom: RgbaImage,
+    pub left: RgbaImage,
+    pub right: RgbaImage,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClawTextures {
+    pub left_arm: RgbaImage,
+    pub right_arm: RgbaImage,
+    pub left_leg: RgbaImage,
+    pub right_leg: RgbaImage,
+}
+
+/// The textures of every feature enabled in an [`EarsFeatures`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct FeatureTextures {
+    pub ears: Option<EarTextures>,
+    pub tail: Option<RgbaImage>,
+    pub snout: Option<SnoutTextures>,
+    pub horn: Option<RgbaImage>,
+    pub halo: Option<RgbaImage>,
+    pub claws: Option<ClawTextures>,
+}
+
+/// Crops the texture of every feature enabled in `features` out of `image`. Returns an error if
+/// `image` doesn't have the size of a skin.
+pub fn extract_feature_textures(
+    image: &RgbaImage,
+    features: &EarsFeatures,
+) -> Result<FeatureTextures> {
+    use FeatureTexture::*;
+
+    let Some(scale) = skin_scale(image) else {
+        return Err(EarsError::InvalidSkinSize(image.width(), image.height()));
+    };
+    let mut textures: HashMap<FeatureTexture, RgbaImage> = feature_regions(features)
+        .into_iter()
+        .map(|region| (region.texture, region.crop(image, scale)))
+        .collect();
+    let mut take = |texture| textures.remove(&texture);
+    let mut plane = |front, back| {
+        Some(PlaneTextures {
+            front: take(front)?,
+            back: take(back)?,
+        })
+    };
+
+    let ears = match ear_layout(features.ear_mode) {
+        Some(EarLayout::Single) => plane(EarFront, EarBack).map(EarTextures::Single),
+        Some(EarLayout::Around) => Some(EarTextures::Around {
+            top: plane(EarFront, EarBack).unwrap(),
+            left: plane(LeftEarSideFront, LeftEarSideBack).unwrap(),
+            right: plane(RightEarSideFront, RightEarSideBack).unwrap(),
+        }),
+        Some(EarLayout::Pair) => Some(EarTextures::Pair {
+            left: plane(LeftEarFront, LeftEarBack).unwrap(),
+            right: plane(RightEarFront, RightEarBack).unwrap(),
+        }),
+        None => None,
+    };
+
+    Ok(FeatureTextures {
+        ears,
+        tail: take(Tail),
+        snout: take(SnoutFront).map(|front| SnoutTextures {
+            front,
+            top: take(SnoutTop).unwrap(),
+            bottom: take(SnoutBottom).unwrap(),
+            left: take(SnoutLeft).unwrap(),
+            right: take(SnoutRight).unwrap(),
+        }),
+        horn: take(Horn),
+        halo: take(Halo),
+        claws: take(LeftArmClaw).map(|left_arm| ClawTextures {
+            left_arm,
+            right_arm: take(RightArmClaw).unwrap(),
+            left_leg: take(LeftLegClaw).unwrap(),
+            right_leg: take(RightLegClaw).unwrap(),
+        }),
+    })
+}
+
+impl FeatureTextures {
+    /// Returns every texture in the set, along with which feature texture it is.
+    pub fn textures(&self) -> Vec<(FeatureTexture, &RgbaImage)> {
+        use FeatureTexture::*;
+
+        let mut textures = Vec::new();
+        fn plane(
+            front: FeatureTexture,
+            back: FeatureTexture,
+            plane: &PlaneTextures,
+        ) -> [(FeatureTexture, &RgbaImage); 2] {
+            [(front, &plane.front), (back, &plane.back)]
+        }
+
+        match &self.ears {
+            Some(EarTextures::Single(top)) => textures.extend(plane(EarFront, EarBack, top)),
+            Some(EarTextures::Around { top, left, right }) => {
+                textures.extend(plane(EarFront, EarBack, top));
+                textures.extend(plane(LeftEarSideFront, LeftEarSideBack, left));
+                textures.extend(plane(RightEarSideFront, RightEarSideBack, right));
+            }
+            Some(EarTextures::Pair { left, right }) => {
+                textures.extend(plane(LeftEarFront, LeftEarBack, left));
+                textures.extend(plane(RightEarFront, RightEarBack, right));
+            }
+            None => {}
+        }
+
+        textures.extend(self.tail.iter().map(|tail| (Tail, tail)));
+        if let Some(snout) = &self.snout {
+            textures.extend([
+                (SnoutFront, &snout.front),
+                (SnoutTop, &snout.top),
+                (SnoutBottom, &snout.bottom),
+                (SnoutLeft, &snout.left),
+                (SnoutRight, &snout.right),
+            ]);
+        }
+        textures.extend(self.horn.iter().map(|horn| (Horn, horn)));
+        textures.extend(self.halo.iter().map(|halo| (Halo, halo)));
+        if let Some(claws) = &self.claws {
+            textures.extend([
+                (LeftArmClaw, &claws.left_arm),
+                (RightArmClaw, &claws.right_arm),
+                (LeftLegClaw, &claws.left_leg),
+                (RightLegClaw, &claws.right_leg),
+            ]);
+        }
+
+        textures
+    }
+}
+
+/// Two enabled feature textures that are read from the same texels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FeatureTextureConflict {
+    pub first: FeatureTexture,
+    pub second: FeatureTexture,
+    /// The texels both textures are read from, in 64x64 skin coordinates.
+    pub region: TextureRegion,
+}
+
+/// Returns every pair of textures in `regions` that share texels.
+pub fn find_texture_conflicts(regions: &[FeatureRegion]) -> Vec<FeatureTextureConflict> {
+    let mut conflicts = Vec::new();
+    for (i, first) in regions.iter().enumerate() {
+        for second in &regions[i + 1..] {
+            if let Some(region) = first.region.intersection(&second.region) {
+                conflicts.push(FeatureTextureConflict {
+                    first: first.texture,
+                    second: second.texture,
+                    region,
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+/// Composites feature textures back into the skin locations Ears reads them from.
+///
+/// Textures for features that aren't enabled in `features` are rejected, as are textures whose
+/// size doesn't match their skin region and images that don't have the size of a skin. Nothing is written if any texture is rejected. Features
+/// without a texture in `textures` are left untouched.
+///
+/// Returns the textures that overlap each other; textures later in [`FeatureTextures::textures`]
+/// are drawn over earlier ones.
+pub fn write_feature_textures(
+    image: &mut RgbaImage,
+    features: &EarsFeatures,
+    textures: &FeatureTextures,
+) -> Result<Vec<FeatureTextureConflict>> {
+    let Some(scale) = skin_scale(image) else {
+        return Err(EarsError::InvalidSkinSize(image.width(), image.height()));
+    };
+    let regions = feature_regions(features);
+
+    let mut writes = Vec::new();
+    for (texture, image) in textures.textures() {
+        let region = regions
+            .iter()
+            .find(|region| region.texture == texture)
+            .ok_or(EarsError::FeatureTextureNotEnabled(texture))?;
+        let scaled = region.region.scaled(scale);
+        if image.dimensions() != (scaled.width, scaled.height) {
+            return Err(EarsError::InvalidFeatureTextureSize(
+                texture,
+                image.width(),
+                image.height(),
+                scaled.width,
+                scaled.height,
+            ));
+        }
+        writes.push((region, scaled, image));
+    }
+
+    for (region, scaled, texture) in writes {
+        if region.flipped {
+            let flipped = imageops::flip_vertical(texture);
+            imageops::replace(image, &flipped, scaled.x.into(), scaled.y.into());
+        } else {
+            imageops::replace(image, texture, scaled.x.into(), scaled.y.into());
+        }
+    }
+
+    Ok(find_texture_conflicts(&regions))
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, imageops::FilterType};
+
+    use super::*;
+    use crate::features::data::{
+        protrusions::Protrusions,
+        snout::SnoutData,
+        tail::{TailData, TailMode},
+    };
+    use crate::parser::EarsParser;
+    use crate::utils::swap_jacket_back_and_tail;
+
+    const SAMPLE: &str = "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png";
+
+    #[test]
+    fn extracts_textures_of_enabled_features() {
+        let image = image::open(SAMPLE).unwrap().to_rgba8();
+        let features = EarsParser::parse(&image).unwrap().unwrap();
+
+        let textures = extract_feature_textures(&image, &features).unwrap();
+
+        let Some(EarTextures::Pair { left, right }) = &textures.ears else {
+            panic!("Out ears should be a pair, got {:?}", textures.ears);
+        };
+        assert_eq!(
+            left.front,
+            imageops::crop_imm(&image, 32, 0, 8, 8).to_image()
+        );
+        assert_eq!(
+            right.back,
+            imageops::crop_imm(&image, 56, 28, 8, 8).to_image()
+        );
+        assert_eq!(
+            textures.tail,
+            Some(imageops::crop_imm(&image, 56, 16, 8, 12).to_image())
+        );
+        let snout = textures.snout.unwrap();
+        assert_eq!(snout.front.dimensions(), (4, 3));
+        assert_eq!(snout.top.dimensions(), (4, 1));
+        assert_eq!(snout.left.dimensions(), (1, 3));
+        assert!(textures.horn.is_some());
+        assert!(textures.halo.is_none());
+        assert!(textures.claws.is_some());
+    }
+
+    #[test]
+    fn extracts_nothing_without_features() {
+        let image = RgbaImage::new(64, 64);
+
+        assert_eq!(
+            extract_feature_textures(&image, &EarsFeatures::default()).unwrap(),
+            FeatureTextures::default()
+        );
+    }
+
+    #[test]
+    fn rejects_images_that_are_not_skins() {
+        let mut image = RgbaImage::new(64, 32);
+
+        assert!(matches!(
+            extract_feature_textures(&image, &EarsFeatures::default()),
+            Err(EarsError::InvalidSkinSize(64, 32))
+        ));
+        assert!(matches!(
+            write_feature_textures(
+                &mut image,
+                &EarsFeatures::default(),
+                &FeatureTextures::default()
+            ),
+            Err(EarsError::InvalidSkinSize(64, 32))
+        ));
+    }
+
+    #[test]
+    fn swapped_tail_is_read_from_the_jacket_back() {
+        let mut image = RgbaImage::new(64, 64);
+        image.put_pixel(32, 47, Rgba([1, 2, 3, 255]));
+        image.put_pixel(56, 16, Rgba([4, 5, 6, 255]));
+        let features = EarsFeatures {
+            tail: Some(TailData {
+                mode: TailMode::Down,
+                swap_jacket_back: true,
+                ..TailData::default()
+            }),
+            ..EarsFeatures::default()
+        };
+
+        let tail = extract_feature_textures(&image, &features)
+            .unwrap()
+            .tail
+            .unwrap();
+
+        let mut swapped = image.clone();
+        swap_jacket_back_and_tail(&mut swapped);
+        assert_eq!(tail, imageops::crop_imm(&swapped, 56, 16, 8, 12).to_image());
+        assert_eq!(*tail.get_pixel(0, 0), Rgba([1, 2, 3, 255]));
+    }
+
+    #[test]
+    fn hd_textures_are_scaled() -> Result<()> {
+        let image = image::open(SAMPLE).unwrap().to_rgba8();
+        let features = EarsFeatures {
+            protrusions: Protrusions::Halo,
+            ..EarsFeatures::default()
+        };
+        let hd_image = imageops::resize(&image, 128, 128, FilterType::Nearest);
+
+        let halo = extract_feature_textures(&image, &features)?.halo.unwrap();
+        let hd_halo = extract_feature_textures(&hd_image, &features)?
+            .halo
+            .unwrap();
+
+        assert_eq!(
+            hd_halo,
+            imageops::resize(&halo, 16, 16, FilterType::Nearest)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn written_textures_roundtrip() -> Result<()> {
+        let image = image::open(SAMPLE).unwrap().to_rgba8();
+        let mut features = EarsParser::parse(&image).unwrap().unwrap();
+        let textures = extract_feature_textures(&image, &features)?;
+
+        let mut out = RgbaImage::new(64, 64);
+        let conflicts = write_feature_textures(&mut out, &features, &textures)?;
+
+        assert_eq!(conflicts, vec![]);
+        assert_eq!(extract_feature_textures(&out, &features)?, textures);
+
+        // The tail ends up in the jacket back when it is swapped
+        features.tail.as_mut().unwrap().swap_jacket_back = true;
+        let mut swapped = RgbaImage::new(64, 64);
+        write_feature_textures(&mut swapped, &features, &textures)?;
+        swap_jacket_back_and_tail(&mut swapped);
+
+        assert_eq!(
+            imageops::crop_imm(&swapped, 56, 16, 8, 12).to_image(),
+            textures.tail.unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn writing_rejects_mismatched_textures() {
+        let features = EarsFeatures {
+            protrusions: Protrusions::Horn,
+            ..EarsFeatures::default()
+        };
+        let mut image = RgbaImage::new(64, 64);
+
+        let wrong_size = FeatureTextures {
+            horn: Some(RgbaImage::new(4, 4)),
+            ..FeatureTextures::default()
+        };
+        assert!(matches!(
+            write_feature_textures(&mut image, &features, &wrong_size),
+            Err(EarsError::InvalidFeatureTextureSize(
+                FeatureTexture::Horn,
+                4,
+                4,
+                8,
+                8
+            ))
+        ));
+
+        let not_enabled = FeatureTextures {
+            halo: Some(RgbaImage::new(8, 8)),
+            ..FeatureTextures::default()
+        };
+        assert!(matches!(
+            write_feature_textures(&mut image, &features, &not_enabled),
+            Err(EarsError::FeatureTextureNotEnabled(FeatureTexture::Halo))
+        ));
+    }
+
+    #[test]
+    fn writing_reports_overlapping_textures() -> Result<()> {
+        let features = EarsFeatures {
+            snout: Some(SnoutData {
+                offset: 0,
+                width: 8,
+                height: 2,
+                depth: 1,
+            }),
+            ..EarsFeatures::default()
+        };
+        let mut image = RgbaImage::new(64, 64);
+
+        let conflicts = write_feature_textures(&mut image, &features, &FeatureTextures::default())?;
+
+        assert_eq!(
+            conflicts,
+            vec![
+                FeatureTextureConflict {
+                    first: FeatureTexture::SnoutTop,
+                    second: FeatureTexture::SnoutLeft,
+                    region: TextureRegion::new(7, 1, 1, 1),
+                },
+                FeatureTextureConflict {
+                    first: FeatureTexture::SnoutBottom,
+                    second: FeatureTexture::SnoutRight,
+                    region: TextureRegion::new(7, 4, 1, 1),
+                },
+            ]
+        );
+
+        Ok(())
+    }
+}
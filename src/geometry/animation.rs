@@ -0,0 +1,215 @@
+use crate::features::EarsFeatures;
+use crate::features::data::tail::TailMode;
+use crate::features::data::wing::WingAnimationMode;
+
+/// What the player is doing, which changes how the tail and wings move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PlayerState {
+    #[default]
+    Idle,
+    Walking,
+    Flying,
+    Sneaking,
+}
+
+/// The animated parts of the Ears features at a point in time.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FeaturePose {
+    /// The rotation of each tail segment relative to the one before it, in degrees. The first
+    /// one is relative to the back, so it includes the tail mode's angle.
+    pub tail: [f32; 4],
+    /// How many degrees pairs of wings are spread further apart than at rest. Single and flat
+    /// wings don't move.
+    pub wing: f32,
+}
+
+impl FeaturePose {
+    /// The pose of `features` when nothing is animated.
+    pub fn rest(features: &EarsFeatures) -> Self {
+        let tail = features.tail.map_or([0.0; 4], |tail| {
+            let [first, rest @ ..] = tail.bends;
+            let [second, third, fourth] = rest;
+            [tail_angle(tail.mode) + first, second, third, fourth]
+        });
+
+        Self { tail, wing: 0.0 }
+    }
+}
+
+/// How far the tail sticks out of the back at rest, in degrees.
+fn tail_angle(mode: TailMode) -> f32 {
+    match mode {
+        TailMode::None
+        | TailMode::Down
+        | TailMode::Cross
+        | TailMode::CrossOverlap
+        | TailMode::Star
+        | TailMode::StarOverlap => 30.0,
+        TailMode::Back | TailMode::Vertical => 90.0,
+        TailMode::Up => 130.0,
+    }
+}
+
+/// How many game ticks Minecraft runs per second. Ears times its animations in ticks.
+const TICKS_PER_SECOND: f32 = 20.0;
+
+/// How far the legs swing in a [`PlayerState`], as Minecraft's limb swing amount from 0 to 1.
+fn limb_swing(state: PlayerState) -> f32 {
+    match state {
+        PlayerState::Walking => 1.0,
+        PlayerState::Idle | PlayerState::Flying | PlayerState::Sneaking => 0.0,
+    }
+}
+
+/// How many degrees the tail swings out at full stride.
+fn tail_swing(mode: TailMode) -> f32 {
+    match mode {
+        TailMode::Back | TailMode::Vertical => 20.0,
+        TailMode::Up => -20.0,
+        _ => 40.0,
+    }
+}
+
+/// Evaluates the tail and wing animations of `features`, `time` seconds into the animation.
+///
+/// This follows `EarsRenderer` in the Ears mod: an animated tail swings out with the legs and
+/// wiggles by `sin(ticks / 12) * 4` degrees, and wings wiggle by `sin((ticks + 8) / 12) * 2`
+/// degrees, or `sin((ticks + 8) / 2) * 20` while flying. Tails that aren't animated keep their
+/// bends, and wings only move with [`WingAnimationMode::Normal`], or
+/// [`WingAnimationMode::NoFlight`] which wiggles the same while flying as while idle.
+pub fn animate_features(features: &EarsFeatures, time: f32, state: PlayerState) -> FeaturePose {
+    let mut pose = FeaturePose::rest(features);
+    let ticks = time * TICKS_PER_SECOND;
+
+    if let Some(tail) = features.tail
+        && tail.animate
+    {
+        pose.tail[0] += tail_swing(tail.mode) * limb_swing(state) + (ticks / 12.0).sin() * 4.0;
+    }
+
+    if let Some(wing) = features.wing
+        && wing.animation_mode != WingAnimationMode::None
+    {
+        let flying =
+            state == PlayerState::Flying && wing.animation_mode != WingAnimationMode::NoFlight;
+        let (speed, amount) = if flying { (2.0, 20.0) } else { (12.0, 2.0) };
+        // Ears turns the left wing by -120 + wiggle and the right one by -60 - wiggle, so a
+        // positive wiggle brings them together
+        let wiggle = ((ticks + 8.0) / speed).sin() * amount;
+        pose.wing = -wiggle;
+    }
+
+    pose
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::data::tail::TailData;
+    use crate::features::data::wing::WingData;
+    use crate::geometry::{ModelElement, Quad, Side, feature_geometry, posed_feature_geometry};
+
+    fn features(animate: bool, animation_mode: WingAnimationMode) -> EarsFeatures {
+        EarsFeatures {
+            tail: Some(TailData {
+                mode: TailMode::Down,
+                segments: 2,
+                bends: [10.0, -20.0, 0.0, 0.0],
+                animate,
+                swap_jacket_back: false,
+            }),
+            wing: Some(WingData {
+                mode: Default::default(),
+                animation_mode,
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn rest_pose_uses_the_tail_bends() {
+        let pose = FeaturePose::rest(&features(true, WingAnimationMode::Normal));
+
+        assert_eq!(pose.tail, [40.0, -20.0, 0.0, 0.0]);
+        assert_eq!(pose.wing, 0.0);
+    }
+
+    #[test]
+    fn static_features_do_not_move() {
+        let features = features(false, WingAnimationMode::None);
+
+        for time in [0.0, 0.3, 1.7] {
+            assert_eq!(
+                animate_features(&features, time, PlayerState::Walking),
+                FeaturePose::rest(&features)
+            );
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!(
+            (actual - expected).abs() < 1e-3,
+            "{actual} is not {expected}"
+        );
+    }
+
+    #[test]
+    fn tail_swings_with_the_legs_and_wiggles_over_time() {
+        let features = features(true, WingAnimationMode::Normal);
+        let tail = |time, state| animate_features(&features, time, state).tail;
+
+        assert_eq!(tail(0.0, PlayerState::Idle), [40.0, -20.0, 0.0, 0.0]);
+        assert_eq!(tail(0.0, PlayerState::Walking), [80.0, -20.0, 0.0, 0.0]);
+        // 6 ticks in, the tail has wiggled by sin(0.5) * 4 degrees
+        assert_close(tail(0.3, PlayerState::Idle)[0], 41.9177);
+        assert_close(tail(0.3, PlayerState::Sneaking)[0], 41.9177);
+        assert_eq!(tail(0.3, PlayerState::Idle)[1..], [-20.0, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn wings_only_flap_harder_when_flight_is_animated() {
+        let normal = features(true, WingAnimationMode::Normal);
+        let no_flight = features(true, WingAnimationMode::NoFlight);
+        let wing = |features, time, state| animate_features(features, time, state).wing;
+
+        assert_close(wing(&normal, 0.0, PlayerState::Idle), -1.2367);
+        assert_close(wing(&normal, 0.0, PlayerState::Flying), 15.1360);
+        assert_close(wing(&normal, 0.5, PlayerState::Flying), -8.2424);
+        for time in [0.0, 0.5, 1.2] {
+            assert_eq!(
+                wing(&no_flight, time, PlayerState::Flying),
+                wing(&no_flight, time, PlayerState::Idle)
+            );
+        }
+    }
+
+    #[test]
+    fn posed_geometry_moves_the_tail_and_wings() {
+        let features = features(true, WingAnimationMode::Normal);
+        let rest = feature_geometry(&features);
+
+        assert_eq!(
+            posed_feature_geometry(&features, &FeaturePose::rest(&features)),
+            rest
+        );
+
+        let flying = posed_feature_geometry(
+            &features,
+            &animate_features(&features, 0.1, PlayerState::Flying),
+        );
+        assert_eq!(flying.len(), rest.len());
+        for element in [
+            ModelElement::TailSegment(0),
+            ModelElement::Wing(Some(Side::Left)),
+        ] {
+            let moved = |quads: &[Quad]| {
+                quads
+                    .iter()
+                    .filter(|q| q.element == element)
+                    .map(|q| q.vertices)
+                    .collect::<Vec<_>>()
+            };
+            assert_ne!(moved(&flying), moved(&rest));
+        }
+    }
+}
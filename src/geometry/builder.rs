@@ -0,0 +1,285 @@
+use crate::features::textures::FeatureRegion;
+use crate::geometry::math::{Mat4, cross, dot, normalize, sub};
+use crate::geometry::{BodyPart, ModelElement, Quad, QuadTexture, Vertex};
+use crate::layout::WING_SIZE;
+
+/// How a texture is turned when it is put on a quad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum TexRotation {
+    None,
+    Clockwise,
+    CounterClockwise,
+}
+
+/// A rectangle of a texture, in texels, and how it is laid on a quad.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Uv {
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    pub(crate) width: f32,
+    pub(crate) height: f32,
+    pub(crate) rotation: TexRotation,
+    pub(crate) flip_horizontal: bool,
+    pub(crate) flip_vertical: bool,
+}
+
+impl Uv {
+    pub(crate) const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            rotation: TexRotation::None,
+            flip_horizontal: false,
+            flip_vertical: false,
+        }
+    }
+
+    pub(crate) fn from_region(region: &FeatureRegion) -> Self {
+        let r = region.region;
+        Self {
+            flip_vertical: region.flipped,
+            ..Self::new(r.x as f32, r.y as f32, r.width as f32, r.height as f32)
+        }
+    }
+
+    pub(crate) fn rotated(self, rotation: TexRotation) -> Self {
+        Self { rotation, ..self }
+    }
+
+    pub(crate) fn flipped_horizontally(self) -> Self {
+        Self {
+            flip_horizontal: !self.flip_horizontal,
+            ..self
+        }
+    }
+
+    /// Returns the rows `y..y + height` of this texture, counted as the texture is seen on the
+    /// quad, before any rotation.
+    pub(crate) fn rows(self, y: f32, height: f32) -> Self {
+        let y = if self.flip_vertical {
+            self.y + self.height - y - height
+        } else {
+            self.y + y
+        };
+        Self { y, height, ..self }
+    }
+
+    /// Returns the columns `x..x + width` of this texture, counted as the texture is seen on the
+    /// quad, before any rotation.
+    pub(crate) fn columns(self, x: f32, width: f32) -> Self {
+        let x = if self.flip_horizontal {
+            self.x + self.width - x - width
+        } else {
+            self.x + x
+        };
+        Self { x, width, ..self }
+    }
+
+    /// The texel coordinates of the top-left, bottom-left, bottom-right and top-right corners of
+    /// a quad using this texture.
+    fn corners(&self) -> [[f32; 2]; 4] {
+        let (x1, y1) = (self.x, self.y);
+        let (x2, y2) = (self.x + self.width, self.y + self.height);
+        let [mut tl, mut bl, mut br, mut tr] = [[x1, y1], [x1, y2], [x2, y2], [x2, y1]];
+
+        if self.flip_horizontal {
+            (tl, tr, bl, br) = (tr, tl, br, bl);
+        }
+        if self.flip_vertical {
+            (tl, bl, tr, br) = (bl, tl, br, tr);
+        }
+
+        match self.rotation {
+            TexRotation::None => [tl, bl, br, tr],
+            TexRotation::Clockwise => [bl, br, tr, tl],
+            TexRotation::CounterClockwise => [tr, tl, bl, br],
+        }
+    }
+}
+
+/// The textures of the six faces of a cuboid, in the order east (`+X`), west (`-X`), top,
+/// bottom, front and back.
+pub(crate) type CuboidUvs = [Option<Uv>; 6];
+
+/// The vanilla texture layout of a `width` x `height` x `depth` cuboid starting at (`u`, `v`).
+pub(crate) fn box_uvs(u: f32, v: f32, width: f32, height: f32, depth: f32) -> CuboidUvs {
+    [
+        Some(Uv::new(u + depth + width, v + depth, depth, height)),
+        Some(Uv::new(u, v + depth, depth, height)),
+        Some(Uv::new(u + depth, v, width, depth)),
+        Some(Uv::new(u + depth + width, v, width, depth)),
+        Some(Uv::new(u + depth, v + depth, width, height)),
+        Some(Uv::new(u + depth * 2.0 + width, v + depth, width, height)),
+    ]
+}
+
+/// Builds quads the way Ears' render delegate does, with a stack of transformations.
+///
+/// After [`GeometryBuilder::anchor_to`], coordinates are relative to the top corner of the body
+/// part on the player's right front, with `+X` going to the player's left, `+Y` going down and
+/// `+Z` going to the back, like in Minecraft's model space. Planes are drawn from the origin
+/// towards `+X` and `+Y`, with their front facing `-Z`.
+pub(crate) struct GeometryBuilder {
+    transform: Mat4,
+    stack: Vec<Mat4>,
+    part: BodyPart,
+    element: ModelElement,
+    texture: QuadTexture,
+    quads: Vec<Quad>,
+}
+
+impl GeometryBuilder {
+    pub(crate) fn new() -> Self {
+        Self {
+            transform: Mat4::IDENTITY,
+            stack: Vec::new(),
+            part: BodyPart::Torso,
+            element: ModelElement::Base,
+            texture: QuadTexture::Skin,
+            quads: Vec::new(),
+        }
+    }
+
+    pub(crate) fn finish(self) -> Vec<Quad> {
+        self.quads
+    }
+
+    /// Moves the origin to the corner of `part` and attaches the next quads to it.
+    pub(crate) fn anchor_to(&mut self, part: BodyPart) {
+        let [x, y, z] = match part {
+            BodyPart::Head => [-4.0, 32.0, 4.0],
+            BodyPart::Torso => [-4.0, 24.0, 2.0],
+            BodyPart::LeftArm => [4.0, 24.0, 2.0],
+            BodyPart::RightArm => [-8.0, 24.0, 2.0],
+            BodyPart::LeftLeg => [0.0, 12.0, 2.0],
+            BodyPart::RightLeg => [-4.0, 12.0, 2.0],
+        };
+
+        self.part = part;
+        self.transform = Mat4::translation(x, y, z) * Mat4::scale(1.0, -1.0, -1.0);
+    }
+
+    pub(crate) fn element(&mut self, element: ModelElement) {
+        self.element = element;
+    }
+
+    pub(crate) fn texture(&mut self, texture: QuadTexture) {
+        self.texture = texture;
+    }
+
+    pub(crate) fn push(&mut self) {
+        self.stack.push(self.transform);
+    }
+
+    pub(crate) fn pop(&mut self) {
+        self.transform = self
+            .stack
+            .pop()
+            .expect("unbalanced geometry transform stack");
+    }
+
+    pub(crate) fn translate(&mut self, x: f32, y: f32, z: f32) {
+        self.transform = self.transform * Mat4::translation(x, y, z);
+    }
+
+    pub(crate) fn rotate(&mut self, degrees: f32, x: f32, y: f32, z: f32) {
+        if degrees != 0.0 {
+            self.transform = self.transform * Mat4::rotation(degrees, [x, y, z]);
+        }
+    }
+
+    /// Draws the front of a `width` x `height` plane.
+    pub(crate) fn front(&mut self, width: f32, height: f32, uv: Uv) {
+        let corners = [
+            [0.0, 0.0, 0.0],
+            [0.0, height, 0.0],
+            [width, height, 0.0],
+            [width, 0.0, 0.0],
+        ];
+        self.face(corners, uv, [0.0, 0.0, -1.0]);
+    }
+
+    /// Draws the back of a `width` x `height` plane, with the texture read from behind.
+    pub(crate) fn back(&mut self, width: f32, height: f32, uv: Uv) {
+        let corners = [
+            [width, 0.0, 0.0],
+            [width, height, 0.0],
+            [0.0, height, 0.0],
+            [0.0, 0.0, 0.0],
+        ];
+        self.face(corners, uv, [0.0, 0.0, 1.0]);
+    }
+
+    /// Draws both sides of a `width` x `height` plane with the same texture, mirrored on the
+    /// back.
+    pub(crate) fn double_sided(&mut self, width: f32, height: f32, uv: Uv) {
+        let corners = [
+            [0.0, 0.0, 0.0],
+            [0.0, height, 0.0],
+            [width, height, 0.0],
+            [width, 0.0, 0.0],
+        ];
+        self.face(corners, uv, [0.0, 0.0, -1.0]);
+        self.face(corners, uv, [0.0, 0.0, 1.0]);
+    }
+
+    /// Draws a `size` cuboid starting at `from`, grown by `inflate` in every direction.
+    pub(crate) fn cuboid(&mut self, from: [f32; 3], size: [f32; 3], uvs: CuboidUvs, inflate: f32) {
+        let [x1, y1, z1] = from.map(|c| c - inflate);
+        let [x2, y2, z2] = [0, 1, 2].map(|i| from[i] + size[i] + inflate);
+
+        let v0 = [x1, y1, z1];
+        let v1 = [x2, y1, z1];
+        let v2 = [x2, y2, z1];
+        let v3 = [x1, y2, z1];
+        let v4 = [x1, y1, z2];
+        let v5 = [x2, y1, z2];
+        let v6 = [x2, y2, z2];
+        let v7 = [x1, y2, z2];
+
+        let faces = [
+            ([v1, v2, v6, v5], [1.0, 0.0, 0.0]),
+            ([v4, v7, v3, v0], [-1.0, 0.0, 0.0]),
+            ([v4, v0, v1, v5], [0.0, -1.0, 0.0]),
+            ([v7, v3, v2, v6], [0.0, 1.0, 0.0]),
+            ([v0, v3, v2, v1], [0.0, 0.0, -1.0]),
+            ([v5, v6, v7, v4], [0.0, 0.0, 1.0]),
+        ];
+
+        for ((corners, normal), uv) in faces.into_iter().zip(uvs) {
+            if let Some(uv) = uv {
+                self.face(corners, uv, normal);
+            }
+        }
+    }
+
+    /// Adds a quad with `corners` given in the order of [`Uv::corners`], facing `normal`.
+    fn face(&mut self, corners: [[f32; 3]; 4], uv: Uv, normal: [f32; 3]) {
+        let (width, height) = match self.texture {
+            QuadTexture::Skin | QuadTexture::Displaced => (64.0, 64.0),
+            QuadTexture::Wing => (WING_SIZE.0 as f32, WING_SIZE.1 as f32),
+        };
+
+        let uvs = uv.corners();
+        let mut vertices = [0, 1, 2, 3].map(|i| Vertex {
+            position: self.transform.transform_point(corners[i]),
+            uv: [uvs[i][0] / width, uvs[i][1] / height],
+        });
+        let normal = normalize(self.transform.transform_vector(normal));
+
+        let [a, b, c, _] = vertices.map(|v| v.position);
+        if dot(cross(sub(b, a), sub(c, a)), normal) < 0.0 {
+            vertices.reverse();
+        }
+
+        self.quads.push(Quad {
+            vertices,
+            normal,
+            part: self.part,
+            element: self.element,
+            texture: self.texture,
+        });
+    }
+}
@@ -0,0 +1,356 @@
+use std::collections::HashMap;
+
+use crate::features::EarsFeatures;
+use crate::features::data::ear::{EarAnchor, EarMode};
+use crate::features::data::tail::{TailData, TailMode};
+use crate::features::data::wing::WingMode;
+use crate::features::textures::{FeatureTexture, processed_feature_regions};
+use crate::geometry::animation::FeaturePose;
+use crate::geometry::builder::{GeometryBuilder, TexRotation, Uv};
+use crate::geometry::{BodyPart, ModelElement, Quad, QuadTexture, Side};
+use crate::layout::WING_SIZE;
+
+type Textures = HashMap<FeatureTexture, Uv>;
+
+/// Builds the geometry of every feature enabled in `features`.
+pub fn feature_geometry(features: &EarsFeatures) -> Vec<Quad> {
+    posed_feature_geometry(features, &FeaturePose::rest(features))
+}
+
+/// Builds the geometry of every feature enabled in `features`, with the tail and wings in `pose`.
+pub fn posed_feature_geometry(features: &EarsFeatures, pose: &FeaturePose) -> Vec<Quad> {
+    let textures: Textures = processed_feature_regions(features)
+        .iter()
+        .map(|region| (region.texture, Uv::from_region(region)))
+        .collect();
+    let mut builder = GeometryBuilder::new();
+
+    build_ears(&mut builder, features, &textures);
+    if let Some(tail) = &features.tail {
+        build_tail(&mut builder, tail, pose, &textures);
+    }
+    build_snout(&mut builder, features, &textures);
+    build_protrusions(&mut builder, features, &textures);
+    build_wings(&mut builder, features, pose.wing);
+    build_chest(&mut builder, features.chest_size);
+
+    builder.finish()
+}
+
+fn build_ears(builder: &mut GeometryBuilder, features: &EarsFeatures, textures: &Textures) {
+    use FeatureTexture::*;
+
+    let z = match features.ear_anchor {
+        EarAnchor::Front => 0.0,
+        EarAnchor::Center => 4.0,
+        EarAnchor::Back => 8.0,
+    };
+    let uv = |texture| textures[&texture];
+
+    builder.anchor_to(BodyPart::Head);
+    builder.element(ModelElement::Ear(None));
+    match features.ear_mode {
+        EarMode::None => {}
+        EarMode::Above | EarMode::Around => {
+            builder.push();
+            builder.translate(-4.0, -8.0, z);
+            builder.front(16.0, 8.0, uv(EarFront));
+            builder.back(16.0, 8.0, uv(EarBack).rotated(TexRotation::Clockwise));
+            builder.pop();
+
+            if features.ear_mode == EarMode::Around {
+                let sides = [
+                    (Side::Right, -4.0, RightEarSideFront, RightEarSideBack),
+                    (Side::Left, 8.0, LeftEarSideFront, LeftEarSideBack),
+                ];
+                for (side, x, front, back) in sides {
+                    builder.element(ModelElement::Ear(Some(side)));
+                    builder.push();
+                    builder.translate(x, 0.0, z);
+                    builder.front(4.0, 8.0, uv(front).rotated(TexRotation::Clockwise));
+                    builder.back(4.0, 8.0, uv(back).rotated(TexRotation::Clockwise));
+                    builder.pop();
+                }
+            }
+        }
+        EarMode::Behind => {
+            builder.push();
+            builder.translate(-4.0, 0.0, 8.0);
+            builder.rotate(-60.0, 1.0, 0.0, 0.0);
+            builder.translate(0.0, -8.0, 0.0);
+            builder.front(16.0, 8.0, uv(EarFront));
+            builder.back(16.0, 8.0, uv(EarBack).rotated(TexRotation::Clockwise));
+            builder.pop();
+        }
+        EarMode::Tall | EarMode::TallCross => {
+            let angles: &[f32] = if features.ear_mode == EarMode::Tall {
+                &[0.0]
+            } else {
+                &[45.0, -45.0]
+            };
+            for angle in angles {
+                builder.push();
+                builder.translate(4.0, -16.0, z);
+                builder.rotate(*angle, 0.0, 1.0, 0.0);
+                builder.translate(-4.0, 0.0, 0.0);
+                builder.front(
+                    8.0,
+                    16.0,
+                    uv(EarFront).rotated(TexRotation::CounterClockwise),
+                );
+                builder.back(8.0, 16.0, uv(EarBack));
+                builder.pop();
+            }
+        }
+        EarMode::Sides | EarMode::Floppy | EarMode::Out | EarMode::Cross => {
+            let ears = [
+                (Side::Right, RightEarFront, RightEarBack),
+                (Side::Left, LeftEarFront, LeftEarBack),
+            ];
+            for (side, front, back) in ears {
+                // Rotating the right ear by a positive angle raises it, the left ear is mirrored
+                let (x, raise) = match side {
+                    Side::Right => (0.0, 1.0),
+                    Side::Left => (8.0, -1.0),
+                };
+                let start = match side {
+                    Side::Right => -8.0,
+                    Side::Left => 0.0,
+                };
+
+                builder.element(ModelElement::Ear(Some(side)));
+                builder.push();
+                match features.ear_mode {
+                    EarMode::Sides => builder.translate(x + start, 0.0, z),
+                    EarMode::Floppy | EarMode::Out => {
+                        let angle = if features.ear_mode == EarMode::Out {
+                            45.0
+                        } else {
+                            -30.0
+                        };
+                        builder.translate(x, 0.0, z);
+                        builder.rotate(angle * raise, 0.0, 0.0, 1.0);
+                        builder.translate(start, 0.0, 0.0);
+                    }
+                    _ => {
+                        builder.translate(4.0, -8.0, z);
+                        builder.rotate(-45.0 * raise, 0.0, 1.0, 0.0);
+                        builder.translate(-4.0, 0.0, 0.0);
+                    }
+                }
+                builder.front(8.0, 8.0, uv(front));
+                builder.back(8.0, 8.0, uv(back));
+                builder.pop();
+            }
+        }
+    }
+}
+
+/// The tail hangs from the lower back, each segment bending further by its rotation in `pose`.
+///
+/// Cross and star tails repeat every segment around the length of the tail. Their overlapping
+/// variants share the same geometry.
+fn build_tail(
+    builder: &mut GeometryBuilder,
+    tail: &TailData,
+    pose: &FeaturePose,
+    textures: &Textures,
+) {
+    let rolls: &[f32] = match tail.mode {
+        TailMode::None => return,
+        TailMode::Down | TailMode::Back | TailMode::Up => &[0.0],
+        TailMode::Vertical => &[90.0],
+        TailMode::Cross | TailMode::CrossOverlap => &[0.0, 90.0],
+        TailMode::Star | TailMode::StarOverlap => &[0.0, 45.0, 90.0, 135.0],
+    };
+    let uv = textures[&FeatureTexture::Tail];
+    let segments = tail.segments.clamp(1, 4);
+    let length = 12.0 / segments as f32;
+
+    builder.anchor_to(BodyPart::Torso);
+    builder.push();
+    builder.translate(0.0, 10.0, 4.0);
+    for segment in 0..segments {
+        builder.element(ModelElement::TailSegment(segment));
+        builder.rotate(pose.tail[segment as usize], 1.0, 0.0, 0.0);
+
+        let segment_uv = uv.rows(segment as f32 * length, length);
+        for roll in rolls {
+            builder.push();
+            builder.translate(4.0, 0.0, 0.0);
+            builder.rotate(*roll, 0.0, 1.0, 0.0);
+            builder.translate(-4.0, 0.0, 0.0);
+            builder.double_sided(8.0, length, segment_uv);
+            builder.pop();
+        }
+
+        builder.translate(0.0, length, 0.0);
+    }
+    builder.pop();
+}
+
+/// The snout is a box on the face, its offset counted up from the bottom of the head.
+fn build_snout(builder: &mut GeometryBuilder, features: &EarsFeatures, textures: &Textures) {
+    use FeatureTexture::*;
+
+    let Some(snout) = features.snout else {
+        return;
+    };
+    let (width, height, depth) = (snout.width as f32, snout.height as f32, snout.depth as f32);
+    let uv = |texture| Some(textures[&texture]);
+
+    builder.anchor_to(BodyPart::Head);
+    builder.element(ModelElement::Snout);
+    builder.cuboid(
+        [
+            (8.0 - width) / 2.0,
+            8.0 - snout.offset as f32 - height,
+            -depth,
+        ],
+        [width, height, depth],
+        [
+            uv(SnoutLeft),
+            uv(SnoutRight),
+            uv(SnoutTop),
+            uv(SnoutBottom),
+            uv(SnoutFront),
+            None,
+        ],
+        0.0,
+    );
+}
+
+fn build_protrusions(builder: &mut GeometryBuilder, features: &EarsFeatures, textures: &Textures) {
+    use FeatureTexture::*;
+
+    let protrusions = features.protrusions;
+
+    if protrusions.has_horn() {
+        builder.anchor_to(BodyPart::Head);
+        builder.element(ModelElement::Horn);
+        builder.push();
+        builder.translate(0.0, 0.0, 2.0);
+        builder.rotate(-25.0, 1.0, 0.0, 0.0);
+        builder.translate(0.0, -8.0, 0.0);
+        builder.double_sided(8.0, 8.0, textures[&Horn]);
+        builder.pop();
+    }
+
+    if protrusions.has_halo() {
+        let count = if protrusions.has_double_halo() { 2 } else { 1 };
+        builder.anchor_to(BodyPart::Head);
+        for index in 0..count {
+            builder.element(ModelElement::Halo(index));
+            builder.push();
+            builder.translate(0.0, -3.0 - 2.0 * index as f32, 0.0);
+            builder.rotate(90.0, 1.0, 0.0, 0.0);
+            builder.double_sided(8.0, 8.0, textures[&Halo]);
+            builder.pop();
+        }
+    }
+
+    if protrusions.has_claws() {
+        builder.element(ModelElement::Claw);
+
+        // Arm claws hang from the outer side of the arms
+        for (part, x, texture) in [
+            (BodyPart::LeftArm, 4.0, LeftArmClaw),
+            (BodyPart::RightArm, 0.0, RightArmClaw),
+        ] {
+            builder.anchor_to(part);
+            builder.push();
+            builder.translate(x, 12.0, 4.0);
+            builder.rotate(90.0, 0.0, 1.0, 0.0);
+            builder.double_sided(4.0, 4.0, textures[&texture]);
+            builder.pop();
+        }
+
+        // Leg claws lie on the ground in front of the feet
+        for (part, texture) in [
+            (BodyPart::LeftLeg, LeftLegClaw),
+            (BodyPart::RightLeg, RightLegClaw),
+        ] {
+            builder.anchor_to(part);
+            builder.push();
+            builder.translate(0.0, 12.0, 0.0);
+            builder.rotate(-90.0, 1.0, 0.0, 0.0);
+            builder.double_sided(4.0, 4.0, textures[&texture]);
+            builder.pop();
+        }
+    }
+}
+
+/// Wings grow from the top of the spine and sample the [`WING_SIZE`] wing texture. Pairs of wings
+/// are spread further apart by `spread` degrees.
+fn build_wings(builder: &mut GeometryBuilder, features: &EarsFeatures, spread: f32) {
+    let Some(wing) = features.wing else {
+        return;
+    };
+    let (width, height) = (WING_SIZE.0 as f32, WING_SIZE.1 as f32);
+    let half = width / 2.0;
+    let full = Uv::new(0.0, 0.0, width, height);
+
+    let wings: &[(Option<Side>, f32, f32, Uv)] = match wing.mode {
+        WingMode::None => &[],
+        WingMode::SymmetricDual => &[
+            (Some(Side::Left), -30.0, width, full),
+            (Some(Side::Right), 30.0, width, full.flipped_horizontally()),
+        ],
+        WingMode::SymmetricSingle => &[(None, -90.0, width, full)],
+        WingMode::AsymmetricL => &[(Some(Side::Left), -30.0, width, full)],
+        WingMode::AsymmetricR => &[(Some(Side::Right), 30.0, width, full)],
+        WingMode::AsymmetricDual => &[
+            (Some(Side::Left), -30.0, half, full.columns(half, half)),
+            (Some(Side::Right), 30.0, half, full.columns(0.0, half)),
+        ],
+        WingMode::Flat => &[(None, 0.0, width, full)],
+    };
+
+    builder.anchor_to(BodyPart::Torso);
+    builder.texture(QuadTexture::Wing);
+    for (side, angle, width, uv) in wings {
+        builder.element(ModelElement::Wing(*side));
+        builder.push();
+        if wing.mode == WingMode::Flat {
+            builder.translate(-2.0, -2.0, 4.0);
+        } else {
+            builder.translate(4.0, -2.0, 4.0);
+            let spread = match side {
+                Some(Side::Left) => -spread,
+                Some(Side::Right) => spread,
+                None => 0.0,
+            };
+            builder.rotate(angle + spread, 0.0, 1.0, 0.0);
+            if *side == Some(Side::Right) {
+                builder.translate(-width, 0.0, 0.0);
+            }
+        }
+        builder.double_sided(*width, height, *uv);
+        builder.pop();
+    }
+    builder.texture(QuadTexture::Skin);
+}
+
+/// The chest is a flap of the torso front, tilted forward further the bigger it is.
+fn build_chest(builder: &mut GeometryBuilder, size: f32) {
+    if size <= 0.0 {
+        return;
+    }
+    let angle = size * 45.0;
+    let sin = angle.to_radians().sin();
+
+    builder.anchor_to(BodyPart::Torso);
+    builder.element(ModelElement::Chest);
+    for (u, v, inflate) in [(20.0, 22.0, 0.0), (20.0, 38.0, 0.25)] {
+        builder.push();
+        builder.translate(0.0, 2.0, -inflate);
+        builder.rotate(-angle, 1.0, 0.0, 0.0);
+        builder.front(8.0, 4.0, Uv::new(u, v, 8.0, 4.0));
+
+        // The underside closes the flap back onto the torso
+        builder.translate(0.0, 4.0, 0.0);
+        builder.rotate(angle + 90.0, 1.0, 0.0, 0.0);
+        builder.front(8.0, 4.0 * sin, Uv::new(u, v + 3.0, 8.0, 1.0));
+        builder.pop();
+    }
+}
@@ -0,0 +1,99 @@
+use std::ops::Mul;
+
+/// A column-major affine transformation matrix.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Mat4(pub(crate) [[f32; 4]; 4]);
+
+impl Mat4 {
+    pub(crate) const IDENTITY: Mat4 = Mat4([
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ]);
+
+    pub(crate) fn translation(x: f32, y: f32, z: f32) -> Mat4 {
+        let mut m = Self::IDENTITY;
+        m.0[3] = [x, y, z, 1.0];
+        m
+    }
+
+    pub(crate) fn scale(x: f32, y: f32, z: f32) -> Mat4 {
+        let mut m = Self::IDENTITY;
+        m.0[0][0] = x;
+        m.0[1][1] = y;
+        m.0[2][2] = z;
+        m
+    }
+
+    /// A right-handed rotation of `degrees` around `axis`.
+    pub(crate) fn rotation(degrees: f32, axis: [f32; 3]) -> Mat4 {
+        let [x, y, z] = normalize(axis);
+        let (s, c) = degrees.to_radians().sin_cos();
+        let t = 1.0 - c;
+
+        Mat4([
+            [t * x * x + c, t * x * y + s * z, t * x * z - s * y, 0.0],
+            [t * x * y - s * z, t * y * y + c, t * y * z + s * x, 0.0],
+            [t * x * z + s * y, t * y * z - s * x, t * z * z + c, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub(crate) fn transform_point(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let m = &self.0;
+        [
+            m[0][0] * x + m[1][0] * y + m[2][0] * z + m[3][0],
+            m[0][1] * x + m[1][1] * y + m[2][1] * z + m[3][1],
+            m[0][2] * x + m[1][2] * y + m[2][2] * z + m[3][2],
+        ]
+    }
+
+    pub(crate) fn transform_vector(&self, [x, y, z]: [f32; 3]) -> [f32; 3] {
+        let m = &self.0;
+        [
+            m[0][0] * x + m[1][0] * y + m[2][0] * z,
+            m[0][1] * x + m[1][1] * y + m[2][1] * z,
+            m[0][2] * x + m[1][2] * y + m[2][2] * z,
+        ]
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut out = [[0.0; 4]; 4];
+        for (col, out_col) in out.iter_mut().enumerate() {
+            for (row, out) in out_col.iter_mut().enumerate() {
+                *out = (0..4).map(|k| self.0[k][row] * rhs.0[col][k]).sum();
+            }
+        }
+        Mat4(out)
+    }
+}
+
+pub(crate) fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn normalize(v: [f32; 3]) -> [f32; 3] {
+    let len = dot(v, v).sqrt();
+    if len == 0.0 {
+        v
+    } else {
+        [v[0] / len, v[1] / len, v[2] / len]
+    }
+}
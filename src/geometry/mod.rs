@@ -0,0 +1,539 @@
+//! Renderer-agnostic geometry of the player model and its Ears features.
+//!
+//! Everything is built from textured quads in model space, measured in skin pixels: `+Y` is up,
+//! the origin is between the player's feet, the player faces `+Z` and `+X` is the player's left.
+//! UVs are normalized to the texture each quad samples, skin UVs pointing into a skin processed
+//! like [`ProcessedSkin`](crate::utils::ProcessedSkin), with the tail already swapped in place.
+
+mod animation;
+mod builder;
+mod features;
+pub(crate) mod math;
+pub(crate) mod player;
+mod pose;
+
+use crate::features::EarsFeatures;
+use crate::features::data::leg::LegMode;
+use crate::utils::SkinModel;
+
+pub use crate::layout::BodyPart;
+pub use animation::{FeaturePose, PlayerState, animate_features};
+pub use features::{feature_geometry, posed_feature_geometry};
+pub use player::player_geometry;
+pub use pose::{Pose, apply_pose};
+
+/// A side of the player, from the player's point of view.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+impl Side {
+    fn name(&self) -> &'static str {
+        match self {
+            Side::Left => "left",
+            Side::Right => "right",
+        }
+    }
+}
+
+/// What a [`Quad`] is part of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ModelElement {
+    /// The base layer of a body part.
+    Base,
+    /// The overlay layer of a body part, like the hat or the jacket.
+    Overlay,
+    /// An ear, or the single plane spanning the head when there is no side.
+    Ear(Option<Side>),
+    /// A segment of the tail, starting at 0 for the one closest to the body.
+    TailSegment(u8),
+    Snout,
+    Horn,
+    /// A halo, 0 being the lower one.
+    Halo(u8),
+    Claw,
+    /// A wing, or the single wing in the middle of the back when there is no side.
+    Wing(Option<Side>),
+    Chest,
+}
+
+impl ModelElement {
+    /// A name for this element on `part`, unique within a model, like `ear_left` or
+    /// `tail_segment_2`.
+    pub fn name(&self, part: BodyPart) -> String {
+        match self {
+            ModelElement::Base => part.name().to_string(),
+            ModelElement::Overlay => part.overlay_name().to_string(),
+            ModelElement::Ear(None) => "ear".to_string(),
+            ModelElement::Ear(Some(side)) => format!("ear_{}", side.name()),
+            ModelElement::TailSegment(segment) => format!("tail_segment_{}", segment + 1),
+            ModelElement::Snout => "snout".to_string(),
+            ModelElement::Horn => "horn".to_string(),
+            ModelElement::Halo(0) => "halo".to_string(),
+            ModelElement::Halo(index) => format!("halo_{}", index + 1),
+            ModelElement::Claw => format!("{}_claw", part.name()),
+            ModelElement::Wing(None) => "wing".to_string(),
+            ModelElement::Wing(Some(side)) => format!("wing_{}", side.name()),
+            ModelElement::Chest => "chest".to_string(),
+        }
+    }
+
+    /// Whether this element is an Ears feature rather than part of the vanilla model.
+    pub fn is_feature(&self) -> bool {
+        !matches!(self, ModelElement::Base | ModelElement::Overlay)
+    }
+}
+
+/// The texture a [`Quad`] samples.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum QuadTexture {
+    Skin,
+    /// The displaced leg texture, laid out like the skin, that digitigrade legs are drawn with.
+    Displaced,
+    /// The wing texture stored in the Alfalfa data.
+    Wing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vertex {
+    pub position: [f32; 3],
+    pub uv: [f32; 2],
+}
+
+/// A textured quad of the model.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quad {
+    /// The corners of the quad, counter-clockwise when seen from the side it faces.
+    pub vertices: [Vertex; 4],
+    pub normal: [f32; 3],
+    pub part: BodyPart,
+    pub element: ModelElement,
+    pub texture: QuadTexture,
+}
+
+/// Builds the whole model of a player with `features` and the arms of `model`, base player
+/// included.
+pub fn model_geometry(features: &EarsFeatures, model: SkinModel) -> Vec<Quad> {
+    let mut quads = player_geometry(features.leg_mode, model);
+    quads.extend(feature_geometry(features));
+    fit_arm_features(&mut quads, model);
+    quads
+}
+
+/// Builds the player model without any Ears features.
+pub fn vanilla_geometry(model: SkinModel) -> Vec<Quad> {
+    player_geometry(LegMode::Plantigrade, model)
+}
+
+/// Moves the features on the arms, which are built against classic arms, to the outer side of
+/// the arms of `model`.
+pub(crate) fn fit_arm_features(quads: &mut [Quad], model: SkinModel) {
+    if model == SkinModel::Classic {
+        return;
+    }
+
+    for quad in quads.iter_mut().filter(|q| q.element.is_feature()) {
+        let dx = match quad.part {
+            BodyPart::LeftArm => -1.0,
+            BodyPart::RightArm => 1.0,
+            _ => continue,
+        };
+        for vertex in &mut quad.vertices {
+            vertex.position[0] += dx;
+            vertex.position[1] -= 0.5;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::math::{cross, dot, normalize, sub};
+    use super::*;
+    use crate::features::data::ear::{EarAnchor, EarMode};
+    use crate::features::data::protrusions::Protrusions;
+    use crate::features::data::snout::SnoutData;
+    use crate::features::data::tail::{TailData, TailMode};
+    use crate::features::data::wing::{WingData, WingMode};
+    use crate::parser::EarsParser;
+    use crate::utils::errors::Result;
+    use enum_ordinalize::Ordinalize;
+
+    fn assert_well_formed(quads: &[Quad]) {
+        for quad in quads {
+            let [a, b, c, _] = quad.vertices.map(|v| v.position);
+            let winding = normalize(cross(sub(b, a), sub(c, a)));
+            assert!(dot(winding, quad.normal) > 0.99, "{quad:?}");
+
+            assert!(
+                quad.vertices
+                    .iter()
+                    .flat_map(|v| v.uv)
+                    .all(|uv| (0.0..=1.0).contains(&uv)),
+                "{quad:?}"
+            );
+        }
+    }
+
+    fn bounds(quads: &[Quad]) -> ([f32; 3], [f32; 3]) {
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for position in quads.iter().flat_map(|q| q.vertices.map(|v| v.position)) {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+        (min, max)
+    }
+
+    fn assert_close(a: [f32; 3], b: [f32; 3]) {
+        assert!((0..3).all(|i| (a[i] - b[i]).abs() < 1e-4), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn vanilla_model_is_a_player() {
+        let quads = vanilla_geometry(SkinModel::Classic);
+
+        assert_eq!(quads.len(), 6 * 6 * 2);
+        assert_well_formed(&quads);
+
+        let base: Vec<_> = quads
+            .iter()
+            .copied()
+            .filter(|q| q.element == ModelElement::Base)
+            .collect();
+        assert_eq!(bounds(&base), ([-8.0, 0.0, -4.0], [8.0, 32.0, 4.0]));
+
+        let head: Vec<_> = base
+            .iter()
+            .copied()
+            .filter(|q| q.part == BodyPart::Head)
+            .collect();
+        // The face is the head's front
+        let face = head.iter().find(|q| q.normal[2] > 0.99).unwrap();
+        let uvs = face.vertices.map(|v| v.uv);
+        assert!(uvs.contains(&[8.0 / 64.0, 8.0 / 64.0]));
+        assert!(uvs.contains(&[16.0 / 64.0, 16.0 / 64.0]));
+    }
+
+    #[test]
+    fn face_texture_is_not_mirrored() {
+        let quads = vanilla_geometry(SkinModel::Classic);
+        let face = quads
+            .iter()
+            .find(|q| {
+                q.part == BodyPart::Head && q.element == ModelElement::Base && q.normal[2] > 0.99
+            })
+            .unwrap();
+
+        // The left edge of the face texture is on the player's right
+        for vertex in face.vertices {
+            let expected_u = if vertex.position[0] < 0.0 { 8.0 } else { 16.0 };
+            assert_eq!(vertex.uv[0] * 64.0, expected_u);
+            let expected_v = if vertex.position[1] > 28.0 { 8.0 } else { 16.0 };
+            assert_eq!(vertex.uv[1] * 64.0, expected_v);
+        }
+    }
+
+    #[test]
+    fn ear_anchor_moves_ears() {
+        let mut features = EarsFeatures {
+            ear_mode: EarMode::Above,
+            ear_anchor: EarAnchor::Front,
+            ..Default::default()
+        };
+        let front = feature_geometry(&features);
+        features.ear_anchor = EarAnchor::Back;
+        let back = feature_geometry(&features);
+
+        assert_eq!(front.len(), 2);
+        assert_well_formed(&front);
+        assert_eq!(bounds(&front), ([-8.0, 32.0, 4.0], [8.0, 40.0, 4.0]));
+        assert_eq!(bounds(&back), ([-8.0, 32.0, -4.0], [8.0, 40.0, -4.0]));
+    }
+
+    #[test]
+    fn every_ear_mode_has_geometry() {
+        for mode in EarMode::VARIANTS {
+            let quads = feature_geometry(&EarsFeatures {
+                ear_mode: *mode,
+                ..Default::default()
+            });
+
+            assert_eq!(quads.is_empty(), *mode == EarMode::None, "{mode:?}");
+            assert_well_formed(&quads);
+            assert!(quads.iter().all(|q| q.part == BodyPart::Head));
+        }
+    }
+
+    #[test]
+    fn tail_has_one_element_per_segment() {
+        let tail = |mode, segments| {
+            let features = EarsFeatures {
+                tail: Some(TailData {
+                    mode,
+                    segments,
+                    bends: [10.0, 20.0, -20.0, 0.0],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            feature_geometry(&features)
+        };
+
+        let quads = tail(TailMode::Down, 3);
+        assert_eq!(quads.len(), 3 * 2);
+        assert_well_formed(&quads);
+        assert!(quads.iter().all(|q| q.part == BodyPart::Torso));
+        assert!(
+            quads
+                .iter()
+                .any(|q| q.element == ModelElement::TailSegment(2))
+        );
+
+        assert_eq!(tail(TailMode::Star, 1).len(), 4 * 2);
+        assert!(tail(TailMode::None, 2).is_empty());
+    }
+
+    #[test]
+    fn digitigrade_legs_read_the_displaced_texture() {
+        let textures = |leg_mode, element| {
+            let mut textures: Vec<QuadTexture> = player_geometry(leg_mode, SkinModel::Classic)
+                .into_iter()
+                .filter(|q| q.part == BodyPart::LeftLeg && q.element == element)
+                .map(|q| q.texture)
+                .collect();
+            textures.dedup();
+            textures
+        };
+
+        for element in [ModelElement::Base, ModelElement::Overlay] {
+            assert_eq!(textures(LegMode::Plantigrade, element), [QuadTexture::Skin]);
+            // The thigh keeps the upper half of the leg, the shin is displaced
+            assert_eq!(
+                textures(LegMode::DigitigradePartial, element),
+                [QuadTexture::Skin, QuadTexture::Displaced]
+            );
+            assert_eq!(
+                textures(LegMode::DigitigradeFull, element),
+                [QuadTexture::Displaced]
+            );
+        }
+    }
+
+    #[test]
+    fn swapped_tail_is_read_from_the_tail_texture() {
+        let tail = |swap_jacket_back| {
+            let features = EarsFeatures {
+                tail: Some(TailData {
+                    mode: TailMode::Down,
+                    swap_jacket_back,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            };
+            feature_geometry(&features)
+        };
+
+        // A processed skin has the tail back where it is when it isn't swapped
+        assert_eq!(tail(true), tail(false));
+        for uv in tail(true).iter().flat_map(|q| q.vertices.map(|v| v.uv)) {
+            assert!((56.0..=64.0).contains(&(uv[0] * 64.0)), "{uv:?}");
+            assert!((16.0..=28.0).contains(&(uv[1] * 64.0)), "{uv:?}");
+        }
+    }
+
+    #[test]
+    fn snout_sticks_out_of_the_face() {
+        let features = EarsFeatures {
+            snout: Some(SnoutData {
+                offset: 1,
+                width: 4,
+                height: 3,
+                depth: 2,
+            }),
+            ..Default::default()
+        };
+        let quads = feature_geometry(&features);
+
+        assert_eq!(quads.len(), 5);
+        assert_well_formed(&quads);
+        assert_eq!(bounds(&quads), ([-2.0, 25.0, 4.0], [2.0, 28.0, 6.0]));
+    }
+
+    #[test]
+    fn sample_features_have_geometry() -> Result<()> {
+        let image = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let features = EarsParser::parse(&image)?.unwrap();
+        let quads = model_geometry(&features, SkinModel::Classic);
+        assert_well_formed(&quads);
+
+        let elements = |part: BodyPart| {
+            quads
+                .iter()
+                .filter(|q| q.part == part && q.element.is_feature())
+                .map(|q| q.element.name(q.part))
+                .collect::<std::collections::HashSet<_>>()
+        };
+
+        assert!(elements(BodyPart::Head).contains("ear_left"));
+        assert!(elements(BodyPart::Head).contains("snout"));
+        assert!(elements(BodyPart::Head).contains("horn"));
+        assert!(elements(BodyPart::Torso).contains("tail_segment_3"));
+        assert!(elements(BodyPart::Torso).contains("wing_right"));
+        assert!(elements(BodyPart::LeftLeg).contains("left_leg_claw"));
+        assert!(
+            quads
+                .iter()
+                .filter(|q| q.texture == QuadTexture::Wing)
+                .all(|q| matches!(q.element, ModelElement::Wing(_)))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn halo_floats_above_the_head() {
+        let quads = feature_geometry(&EarsFeatures {
+            protrusions: Protrusions::DoubleHalo,
+            ..Default::default()
+        });
+
+        assert!(quads.iter().any(|q| q.element == ModelElement::Halo(1)));
+        let (min, _) = bounds(&quads);
+        assert!(min[1] > 32.0);
+    }
+
+    #[test]
+    fn wings_follow_the_wing_mode() {
+        let wings = |mode| {
+            feature_geometry(&EarsFeatures {
+                wing: Some(WingData {
+                    mode,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+        };
+
+        assert_eq!(wings(WingMode::SymmetricDual).len(), 4);
+        assert_eq!(wings(WingMode::AsymmetricL).len(), 2);
+        assert!(
+            wings(WingMode::AsymmetricL)
+                .iter()
+                .all(|q| q.element == ModelElement::Wing(Some(Side::Left))
+                    && q.vertices.iter().all(|v| v.position[0] >= 0.0))
+        );
+        assert!(wings(WingMode::None).is_empty());
+    }
+
+    #[test]
+    fn flat_wings_cover_the_whole_wing_texture() {
+        let quads = feature_geometry(&EarsFeatures {
+            wing: Some(WingData {
+                mode: WingMode::Flat,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        let (min, max) = bounds(&quads);
+        assert_eq!((max[0] - min[0], max[1] - min[1]), (20.0, 16.0));
+        for quad in &quads {
+            let us = quad.vertices.map(|v| v.uv[0]);
+            let vs = quad.vertices.map(|v| v.uv[1]);
+            assert!(us.contains(&0.0) && us.contains(&1.0));
+            assert!(vs.contains(&0.0) && vs.contains(&1.0));
+        }
+    }
+
+    #[test]
+    fn pivots_sit_on_their_parts() {
+        let quads = vanilla_geometry(SkinModel::Classic);
+        for part in BodyPart::ALL {
+            let part_quads: Vec<_> = quads
+                .iter()
+                .copied()
+                .filter(|q| q.part == part && q.element == ModelElement::Base)
+                .collect();
+            let (min, max) = bounds(&part_quads);
+            let pivot = part.pivot();
+            assert!((0..3).all(|i| min[i] - 1.0 <= pivot[i] && pivot[i] <= max[i] + 1.0));
+        }
+        assert_close(BodyPart::Head.pivot(), [0.0, 24.0, 0.0]);
+    }
+
+    #[test]
+    fn pose_turns_parts_around_their_pivots() {
+        let mut quads = vanilla_geometry(SkinModel::Classic);
+        let pose = Pose {
+            head: [0.0, 90.0, 0.0],
+            right_arm: [-90.0, 0.0, 0.0],
+            ..Default::default()
+        };
+        apply_pose(&mut quads, &pose);
+        assert_well_formed(&quads);
+
+        let face = quads
+            .iter()
+            .find(|q| {
+                q.part == BodyPart::Head && q.element == ModelElement::Base && q.normal[0] > 0.99
+            })
+            .unwrap();
+        assert!(
+            face.vertices
+                .iter()
+                .all(|v| v.uv[0] * 64.0 >= 8.0 && v.uv[0] * 64.0 <= 16.0)
+        );
+
+        let arm: Vec<_> = quads
+            .iter()
+            .copied()
+            .filter(|q| q.part == BodyPart::RightArm && q.element == ModelElement::Base)
+            .collect();
+        let (min, max) = bounds(&arm);
+        // Raised forward, the hand is 10 pixels in front of the shoulder
+        assert_close([min[1], max[1], max[2]], [20.0, 24.0, 10.0]);
+    }
+
+    #[test]
+    fn slim_arms_are_three_pixels_wide() {
+        let features = EarsFeatures {
+            protrusions: Protrusions::Claws,
+            ..Default::default()
+        };
+        let quads = model_geometry(&features, SkinModel::Slim);
+        let bounds = |part, element| {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for vertex in quads
+                .iter()
+                .filter(|q| q.part == part && q.element == element)
+                .flat_map(|q| q.vertices)
+            {
+                for axis in 0..3 {
+                    min[axis] = min[axis].min(vertex.position[axis]);
+                    max[axis] = max[axis].max(vertex.position[axis]);
+                }
+            }
+            (min, max)
+        };
+
+        let (min, max) = bounds(BodyPart::RightArm, ModelElement::Base);
+        assert_eq!((min[0], max[0], max[1]), (-7.0, -4.0, 23.5));
+        let (min, max) = bounds(BodyPart::LeftArm, ModelElement::Base);
+        assert_eq!((min[0], max[0], max[1]), (4.0, 7.0, 23.5));
+
+        // Claws stay on the outer side of the arms
+        let (min, max) = bounds(BodyPart::RightArm, ModelElement::Claw);
+        assert_eq!((min[0], max[0]), (-7.0, -7.0));
+        let (min, max) = bounds(BodyPart::LeftArm, ModelElement::Claw);
+        assert_eq!((min[0], max[0]), (7.0, 7.0));
+    }
+}
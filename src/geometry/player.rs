@@ -0,0 +1,115 @@
+use crate::features::data::leg::LegMode;
+use crate::geometry::builder::{GeometryBuilder, box_uvs};
+use crate::geometry::{BodyPart, ModelElement, Quad, QuadTexture};
+use crate::layout::{Layer, layer_origin, part_size};
+use crate::utils::SkinModel;
+
+/// The size of a body part, where its base and overlay layers start in the skin, and how much
+/// the overlay is grown by.
+pub(crate) struct PartLayout {
+    pub(crate) part: BodyPart,
+    pub(crate) size: [f32; 3],
+    pub(crate) base: (f32, f32),
+    pub(crate) overlay: (f32, f32),
+    pub(crate) inflate: f32,
+}
+
+/// The layout of every body part, with arms as wide as those of `model`.
+pub(crate) fn part_layouts(model: SkinModel) -> [PartLayout; 6] {
+    BodyPart::ALL.map(|part| {
+        let origin = |layer| {
+            let (u, v) = layer_origin(part, layer);
+            (u as f32, v as f32)
+        };
+
+        PartLayout {
+            part,
+            size: part_size(part, model).map(|c| c as f32),
+            base: origin(Layer::Base),
+            overlay: origin(Layer::Overlay),
+            inflate: if part == BodyPart::Head { 0.5 } else { 0.25 },
+        }
+    })
+}
+
+/// Builds the base and overlay layers of the player model, with the arms of `model`.
+///
+/// Digitigrade legs are bent at the knee, the thigh leaning forward and the shin leaning back.
+/// Their displaced parts, the shin and with full digitigrade legs the thigh too, are drawn with
+/// [`QuadTexture::Displaced`].
+/// Slim arms keep their inner side against the body and hang half a pixel lower, like vanilla's.
+pub fn player_geometry(leg_mode: LegMode, model: SkinModel) -> Vec<Quad> {
+    let mut builder = GeometryBuilder::new();
+
+    for layout in &part_layouts(model) {
+        builder.anchor_to(layout.part);
+        match (model, layout.part) {
+            (SkinModel::Slim, BodyPart::RightArm) => builder.translate(1.0, 0.5, 0.0),
+            (SkinModel::Slim, BodyPart::LeftArm) => builder.translate(0.0, 0.5, 0.0),
+            _ => {}
+        }
+
+        let layers = [
+            (ModelElement::Base, layout.base, 0.0),
+            (ModelElement::Overlay, layout.overlay, layout.inflate),
+        ];
+        for (element, (u, v), inflate) in layers {
+            builder.element(element);
+
+            let is_leg = matches!(layout.part, BodyPart::LeftLeg | BodyPart::RightLeg);
+            match leg_mode {
+                LegMode::DigitigradePartial | LegMode::DigitigradeFull if is_leg => {
+                    let (thigh, shin, thigh_texture) = if leg_mode == LegMode::DigitigradeFull {
+                        (-20.0, 40.0, QuadTexture::Displaced)
+                    } else {
+                        (-10.0, 20.0, QuadTexture::Skin)
+                    };
+                    bent_leg(&mut builder, (u, v), inflate, [thigh, shin], thigh_texture);
+                }
+                _ => {
+                    let [width, height, depth] = layout.size;
+                    let uvs = box_uvs(u, v, width, height, depth);
+                    builder.cuboid([0.0; 3], layout.size, uvs, inflate);
+                }
+            }
+        }
+    }
+
+    builder.finish()
+}
+
+/// Draws a 4x12x4 leg split at the knee, the thigh rotated by `thigh` degrees around the hip and
+/// the shin by `shin` more degrees around the knee. The thigh is drawn with `thigh_texture`, the
+/// shin with the displaced texture.
+fn bent_leg(
+    builder: &mut GeometryBuilder,
+    (u, v): (f32, f32),
+    inflate: f32,
+    [thigh, shin]: [f32; 2],
+    thigh_texture: QuadTexture,
+) {
+    let mut uvs = box_uvs(u, v, 4.0, 12.0, 4.0);
+    for face in [0, 1, 4, 5] {
+        uvs[face] = uvs[face].map(|uv| uv.rows(0.0, 6.0));
+    }
+    let mut thigh_uvs = uvs;
+    thigh_uvs[3] = None;
+    let mut shin_uvs = uvs.map(|uv| uv.map(|uv| uv.rows(6.0, 6.0)));
+    shin_uvs[2] = None;
+    shin_uvs[3] = box_uvs(u, v, 4.0, 12.0, 4.0)[3];
+
+    builder.push();
+    builder.translate(2.0, 0.0, 2.0);
+    builder.rotate(thigh, 1.0, 0.0, 0.0);
+    builder.translate(-2.0, 0.0, -2.0);
+    builder.texture(thigh_texture);
+    builder.cuboid([0.0; 3], [4.0, 6.0, 4.0], thigh_uvs, inflate);
+
+    builder.translate(2.0, 6.0, 2.0);
+    builder.rotate(shin, 1.0, 0.0, 0.0);
+    builder.translate(-2.0, 0.0, -2.0);
+    builder.texture(QuadTexture::Displaced);
+    builder.cuboid([0.0; 3], [4.0, 6.0, 4.0], shin_uvs, inflate);
+    builder.texture(QuadTexture::Skin);
+    builder.pop();
+}
@@ -0,0 +1,68 @@
+use crate::geometry::math::{Mat4, normalize};
+use crate::geometry::{BodyPart, Quad};
+
+/// How each body part is rotated around its [pivot](BodyPart::pivot).
+///
+/// Rotations are in degrees around the model's `X`, `Y` and `Z` axes, right-handed, and applied
+/// in that order. Raising an arm forward is a negative `X` rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Pose {
+    pub head: [f32; 3],
+    pub torso: [f32; 3],
+    pub left_arm: [f32; 3],
+    pub right_arm: [f32; 3],
+    pub left_leg: [f32; 3],
+    pub right_leg: [f32; 3],
+}
+
+impl Pose {
+    /// A mid-stride walking pose, swinging the arms and legs by `swing` degrees.
+    pub fn walking(swing: f32) -> Self {
+        Self {
+            left_arm: [swing, 0.0, 0.0],
+            right_arm: [-swing, 0.0, 0.0],
+            left_leg: [-swing, 0.0, 0.0],
+            right_leg: [swing, 0.0, 0.0],
+            ..Default::default()
+        }
+    }
+
+    pub fn rotation(&self, part: BodyPart) -> [f32; 3] {
+        match part {
+            BodyPart::Head => self.head,
+            BodyPart::Torso => self.torso,
+            BodyPart::LeftArm => self.left_arm,
+            BodyPart::RightArm => self.right_arm,
+            BodyPart::LeftLeg => self.left_leg,
+            BodyPart::RightLeg => self.right_leg,
+        }
+    }
+
+    /// The transformation moving `part` from its rest position into this pose.
+    pub(crate) fn transform(&self, part: BodyPart) -> Mat4 {
+        let [x, y, z] = self.rotation(part);
+        let [px, py, pz] = part.pivot();
+
+        Mat4::translation(px, py, pz)
+            * Mat4::rotation(z, [0.0, 0.0, 1.0])
+            * Mat4::rotation(y, [0.0, 1.0, 0.0])
+            * Mat4::rotation(x, [1.0, 0.0, 0.0])
+            * Mat4::translation(-px, -py, -pz)
+    }
+}
+
+/// Moves every quad along with the body part it is attached to.
+pub fn apply_pose(quads: &mut [Quad], pose: &Pose) {
+    if *pose == Pose::default() {
+        return;
+    }
+
+    let transforms = BodyPart::ALL.map(|part| (part, pose.transform(part)));
+    for quad in quads {
+        let (_, transform) = transforms.iter().find(|(p, _)| *p == quad.part).unwrap();
+        for vertex in &mut quad.vertices {
+            vertex.position = transform.transform_point(vertex.position);
+        }
+        quad.normal = normalize(transform.transform_vector(quad.normal));
+    }
+}
@@ -0,0 +1,326 @@
+//! Where every body part of a Minecraft skin is drawn from.
+//!
+//! Every body part has a base layer and an overlay layer, like the head and the hat, each drawn
+//! from a box of the skin with six faces. Regions are given in 64x64 skin coordinates, use
+//! [`TextureRegion::scaled`] with [`skin_scale`] for HD skins. Left and right are from the
+//! player's point of view.
+
+use image::RgbaImage;
+
+use crate::utils::SkinModel;
+
+/// A rectangle of the skin, in 64x64 skin coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextureRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl TextureRegion {
+    pub const fn new(x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    /// Returns the part of both regions that they have in common, if any.
+    pub fn intersection(&self, other: &TextureRegion) -> Option<TextureRegion> {
+        let x1 = self.x.max(other.x);
+        let y1 = self.y.max(other.y);
+        let x2 = (self.x + self.width).min(other.x + other.width);
+        let y2 = (self.y + self.height).min(other.y + other.height);
+
+        (x1 < x2 && y1 < y2).then(|| TextureRegion::new(x1, y1, x2 - x1, y2 - y1))
+    }
+
+    /// Whether the pixel at (`x`, `y`) is inside the region.
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        (self.x..self.x + self.width).contains(&x) && (self.y..self.y + self.height).contains(&y)
+    }
+
+    /// Returns the coordinates of every pixel of the region, row by row.
+    pub fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + use<> {
+        let TextureRegion {
+            x,
+            y,
+            width,
+            height,
+        } = *self;
+        (y..y + height).flat_map(move |y| (x..x + width).map(move |x| (x, y)))
+    }
+
+    /// Returns the region in a skin scaled up `scale` times, like an HD skin.
+    pub fn scaled(&self, scale: u32) -> TextureRegion {
+        TextureRegion::new(
+            self.x * scale,
+            self.y * scale,
+            self.width * scale,
+            self.height * scale,
+        )
+    }
+}
+
+/// A part of the player model that geometry is attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BodyPart {
+    Head,
+    Torso,
+    LeftArm,
+    RightArm,
+    LeftLeg,
+    RightLeg,
+}
+
+impl BodyPart {
+    pub const ALL: [BodyPart; 6] = [
+        BodyPart::Head,
+        BodyPart::Torso,
+        BodyPart::LeftArm,
+        BodyPart::RightArm,
+        BodyPart::LeftLeg,
+        BodyPart::RightLeg,
+    ];
+
+    /// The point this part rotates around, in model space.
+    pub fn pivot(&self) -> [f32; 3] {
+        match self {
+            BodyPart::Head | BodyPart::Torso => [0.0, 24.0, 0.0],
+            BodyPart::LeftArm => [5.0, 22.0, 0.0],
+            BodyPart::RightArm => [-5.0, 22.0, 0.0],
+            BodyPart::LeftLeg => [2.0, 12.0, 0.0],
+            BodyPart::RightLeg => [-2.0, 12.0, 0.0],
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BodyPart::Head => "head",
+            BodyPart::Torso => "body",
+            BodyPart::LeftArm => "left_arm",
+            BodyPart::RightArm => "right_arm",
+            BodyPart::LeftLeg => "left_leg",
+            BodyPart::RightLeg => "right_leg",
+        }
+    }
+
+    /// The name of the overlay layer covering this part.
+    pub fn overlay_name(&self) -> &'static str {
+        match self {
+            BodyPart::Head => "hat",
+            BodyPart::Torso => "jacket",
+            BodyPart::LeftArm => "left_sleeve",
+            BodyPart::RightArm => "right_sleeve",
+            BodyPart::LeftLeg => "left_pants",
+            BodyPart::RightLeg => "right_pants",
+        }
+    }
+}
+
+/// Where the Ears features and the Alfalfa magic pixel are stored.
+pub const DATA_BLOCK: TextureRegion = TextureRegion::new(0, 32, 4, 4);
+/// The colours that glow, if emissive textures are enabled.
+pub const EMISSIVE_PALETTE: TextureRegion = TextureRegion::new(52, 32, 4, 4);
+/// The tail texture, which takes the place of the jacket back when they are swapped.
+pub const TAIL: TextureRegion = TextureRegion::new(56, 16, 8, 12);
+/// The back of the jacket, right below the back of the torso.
+pub const JACKET_BACK: TextureRegion = TextureRegion::new(32, 36, 8, 12);
+/// The width and height of the wing texture, which Ears draws as one 20x16 plane.
+pub const WING_SIZE: (u32, u32) = (20, 16);
+
+/// One of the two layers of a body part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Layer {
+    Base,
+    /// The hat, jacket, sleeves and pants, drawn slightly bigger than the base layer.
+    Overlay,
+}
+
+impl Layer {
+    pub const ALL: [Layer; 2] = [Layer::Base, Layer::Overlay];
+}
+
+/// A face of a body part.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Face {
+    Top,
+    Bottom,
+    Right,
+    Front,
+    Left,
+    Back,
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [
+        Face::Top,
+        Face::Bottom,
+        Face::Right,
+        Face::Front,
+        Face::Left,
+        Face::Back,
+    ];
+
+    /// The face on the other side of a mirrored part, left and right being swapped.
+    pub fn mirrored(&self) -> Face {
+        match self {
+            Face::Right => Face::Left,
+            Face::Left => Face::Right,
+            face => *face,
+        }
+    }
+}
+
+/// Where the box of a layer starts in the skin.
+pub fn layer_origin(part: BodyPart, layer: Layer) -> (u32, u32) {
+    match (part, layer) {
+        (BodyPart::Head, Layer::Base) => (0, 0),
+        (BodyPart::Head, Layer::Overlay) => (32, 0),
+        (BodyPart::Torso, Layer::Base) => (16, 16),
+        (BodyPart::Torso, Layer::Overlay) => (16, 32),
+        (BodyPart::LeftArm, Layer::Base) => (32, 48),
+        (BodyPart::LeftArm, Layer::Overlay) => (48, 48),
+        (BodyPart::RightArm, Layer::Base) => (40, 16),
+        (BodyPart::RightArm, Layer::Overlay) => (40, 32),
+        (BodyPart::LeftLeg, Layer::Base) => (16, 48),
+        (BodyPart::LeftLeg, Layer::Overlay) => (0, 48),
+        (BodyPart::RightLeg, Layer::Base) => (0, 16),
+        (BodyPart::RightLeg, Layer::Overlay) => (0, 32),
+    }
+}
+
+/// The width, height and depth of a body part, in pixels.
+pub fn part_size(part: BodyPart, model: SkinModel) -> [u32; 3] {
+    match part {
+        BodyPart::Head => [8, 8, 8],
+        BodyPart::Torso => [8, 12, 4],
+        BodyPart::LeftArm | BodyPart::RightArm => [model.arm_width(), 12, 4],
+        BodyPart::LeftLeg | BodyPart::RightLeg => [4, 12, 4],
+    }
+}
+
+/// Where `face` of a layer is drawn from.
+pub fn face_region(part: BodyPart, layer: Layer, face: Face, model: SkinModel) -> TextureRegion {
+    let (u, v) = layer_origin(part, layer);
+    let [width, height, depth] = part_size(part, model);
+
+    match face {
+        Face::Top => TextureRegion::new(u + depth, v, width, depth),
+        Face::Bottom => TextureRegion::new(u + depth + width, v, width, depth),
+        Face::Right => TextureRegion::new(u, v + depth, depth, height),
+        Face::Front => TextureRegion::new(u + depth, v + depth, width, height),
+        Face::Left => TextureRegion::new(u + depth + width, v + depth, depth, height),
+        Face::Back => TextureRegion::new(u + 2 * depth + width, v + depth, width, height),
+    }
+}
+
+/// The two rectangles the faces of a layer fill: the top and bottom faces, then the four sides.
+pub fn layer_regions(part: BodyPart, layer: Layer, model: SkinModel) -> [TextureRegion; 2] {
+    let top = face_region(part, layer, Face::Top, model);
+    let right = face_region(part, layer, Face::Right, model);
+    let [width, _, depth] = part_size(part, model);
+
+    [
+        TextureRegion::new(top.x, top.y, width * 2, top.height),
+        TextureRegion::new(right.x, right.y, (width + depth) * 2, right.height),
+    ]
+}
+
+/// Returns the integer factor by which `image` is scaled up from a 64x64 skin, if it is a square
+/// skin whose size is a multiple of 64.
+pub fn skin_scale(image: &RgbaImage) -> Option<u32> {
+    let (width, height) = image.dimensions();
+    if width != height || width < 64 || width % 64 != 0 {
+        return None;
+    }
+    Some(width / 64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn faces_match_the_vanilla_layout() {
+        let face = |part, layer, face, model| face_region(part, layer, face, model);
+
+        assert_eq!(
+            face(BodyPart::Head, Layer::Base, Face::Front, SkinModel::Classic),
+            TextureRegion::new(8, 8, 8, 8)
+        );
+        assert_eq!(
+            face(
+                BodyPart::Head,
+                Layer::Overlay,
+                Face::Back,
+                SkinModel::Classic
+            ),
+            TextureRegion::new(56, 8, 8, 8)
+        );
+        assert_eq!(
+            face(
+                BodyPart::Torso,
+                Layer::Overlay,
+                Face::Left,
+                SkinModel::Classic
+            ),
+            TextureRegion::new(28, 36, 4, 12)
+        );
+        assert_eq!(
+            face(
+                BodyPart::RightArm,
+                Layer::Base,
+                Face::Bottom,
+                SkinModel::Classic
+            ),
+            TextureRegion::new(48, 16, 4, 4)
+        );
+        assert_eq!(
+            face(
+                BodyPart::RightArm,
+                Layer::Base,
+                Face::Bottom,
+                SkinModel::Slim
+            ),
+            TextureRegion::new(47, 16, 3, 4)
+        );
+        assert_eq!(
+            face(
+                BodyPart::LeftArm,
+                Layer::Overlay,
+                Face::Back,
+                SkinModel::Slim
+            ),
+            TextureRegion::new(59, 52, 3, 12)
+        );
+        assert_eq!(
+            layer_regions(BodyPart::LeftLeg, Layer::Base, SkinModel::Classic),
+            [
+                TextureRegion::new(20, 48, 8, 4),
+                TextureRegion::new(16, 52, 16, 12)
+            ]
+        );
+    }
+
+    #[test]
+    fn layers_do_not_overlap() {
+        for model in [SkinModel::Classic, SkinModel::Slim] {
+            let regions: Vec<TextureRegion> = BodyPart::ALL
+                .into_iter()
+                .flat_map(|part| Layer::ALL.map(|layer| (part, layer)))
+                .flat_map(|(part, layer)| layer_regions(part, layer, model))
+                .collect();
+
+            for (i, a) in regions.iter().enumerate() {
+                assert!(a.x + a.width <= 64 && a.y + a.height <= 64, "{a:?}");
+                for b in &regions[i + 1..] {
+                    assert_eq!(a.intersection(b), None, "{a:?} and {b:?}");
+                }
+            }
+        }
+    }
+}
@@ -1,4 +1,8 @@
 pub mod alfalfa;
+pub mod export;
 pub mod features;
+pub mod geometry;
+pub mod layout;
 pub mod parser;
+pub mod render;
 pub mod utils;
@@ -1,7 +1,7 @@
 use image::RgbaImage;
 
-use crate::features::EarsFeatures;
-use crate::parser::utils::to_argb_hex;
+use crate::features::{DataVersion, EarsFeatures};
+use crate::parser::utils::{get_data_pixel, to_argb_hex};
 use crate::utils::errors::Result;
 
 pub(crate) mod utils;
@@ -16,9 +16,9 @@ pub(crate) trait EarsFeaturesParser {
     fn detect_magic_pixel() -> u32;
 
     fn detect(image: &RgbaImage) -> bool {
-        let magic_pixel = image.get_pixel_checked(0, 32);
+        let magic_pixel = get_data_pixel(image, 0, 32);
         if let Some(magic_pixel) = magic_pixel {
-            let magic_pixel = to_argb_hex(magic_pixel);
+            let magic_pixel = to_argb_hex(&magic_pixel);
 
             magic_pixel == Self::detect_magic_pixel()
         } else {
@@ -29,6 +29,16 @@ pub(crate) trait EarsFeaturesParser {
     fn parse(image: &RgbaImage) -> Result<Option<EarsFeatures>>;
 }
 
+/// Writes `features` in the format of their [`DataVersion`], the newest one for custom versions.
+pub(crate) fn write_features(image: &mut RgbaImage, features: &EarsFeatures) -> Result<()> {
+    match features.data_version {
+        DataVersion::V0 => v0::writer::EarsWriterV0::write(image, features),
+        DataVersion::V1(_) | DataVersion::Custom(_) => {
+            v1::writer::EarsWriterV1::write(image, features)
+        }
+    }
+}
+
 pub struct EarsParser;
 
 impl EarsParser {
@@ -1,4 +1,6 @@
-use image::Rgba;
+use image::{Rgba, RgbaImage};
+
+use crate::layout::skin_scale;
 
 pub(crate) fn to_argb_hex(value: &Rgba<u8>) -> u32 {
     (value[3] as u32) << 24 | (value[0] as u32) << 16 | (value[1] as u32) << 8 | value[2] as u32
@@ -9,3 +11,27 @@ pub(crate) fn from_argb_hex(value: u32) -> Rgba<u8> {
 
     Rgba([bytes[1], bytes[2], bytes[3], bytes[0]])
 }
+
+/// Reads the data pixel at `(x, y)` in 64x64 skin coordinates. HD skins store every data pixel
+/// as a scaled block, which is read from its top left texel, like Alfalfa data.
+pub(crate) fn get_data_pixel(image: &RgbaImage, x: u32, y: u32) -> Option<Rgba<u8>> {
+    let scale = skin_scale(image).unwrap_or(1);
+    image.get_pixel_checked(x * scale, y * scale).copied()
+}
+
+/// Writes the data pixel at `(x, y)` in 64x64 skin coordinates, filling its whole block on HD
+/// skins. Returns `false` if the pixel is outside of `image`.
+pub(crate) fn put_data_pixel(image: &mut RgbaImage, x: u32, y: u32, pixel: Rgba<u8>) -> bool {
+    let scale = skin_scale(image).unwrap_or(1);
+    let (x, y) = (x * scale, y * scale);
+    if x + scale > image.width() || y + scale > image.height() {
+        return false;
+    }
+
+    for y in y..y + scale {
+        for x in x..x + scale {
+            image.put_pixel(x, y, pixel);
+        }
+    }
+    true
+}
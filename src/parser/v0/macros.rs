@@ -4,7 +4,7 @@ macro_rules! read_magic_pixel {
            use crate::parser::utils::to_argb_hex;
            use crate::utils::errors::EarsError;
 
-           $image.get_pixel_checked($idx % 4, 32 + ($idx / 4)).ok_or_else(|| EarsError::InvalidMagicPixelLocation($idx)).map(|p| to_argb_hex(&p))
+           crate::parser::utils::get_data_pixel($image, $idx % 4, 32 + ($idx / 4)).ok_or_else(|| EarsError::InvalidMagicPixelLocation($idx)).map(|p| to_argb_hex(&p))
         }
     };
 
@@ -17,7 +17,7 @@ macro_rules! read_magic_pixel {
             use crate::parser::utils::to_argb_hex;
             use crate::utils::errors::EarsError;
 
-            let pixel = to_argb_hex($image.get_pixel_checked($idx % 4, 32 + ($idx / 4)).ok_or_else(|| EarsError::InvalidMagicPixelLocation($idx))?);
+            let pixel = to_argb_hex(&crate::parser::utils::get_data_pixel($image, $idx % 4, 32 + ($idx / 4)).ok_or_else(|| EarsError::InvalidMagicPixelLocation($idx))?);
             let magic_pixel = MagicPixelsV0::get_by_argb_hex(pixel);
 
             Result::Ok(if $relevant {
@@ -38,7 +38,10 @@ use image::RgbaImage;
 pub(crate) use read_magic_pixel;
 
 use crate::{
-    parser::{utils::from_argb_hex, v0::magic_pixels::MagicPixelsV0},
+    parser::{
+        utils::{from_argb_hex, put_data_pixel},
+        v0::magic_pixels::MagicPixelsV0,
+    },
     utils::errors::{EarsError, Result},
 };
 
@@ -56,13 +59,9 @@ pub(crate) fn write_magic_pixel<K: Eq + Hash>(
 }
 
 pub(crate) fn write_raw_magic_pixel(image: &mut RgbaImage, idx: u32, value: u32) -> Result<()> {
-    let magic_pixel = from_argb_hex(value);
-
-    let dst_pixel = image
-        .get_pixel_mut_checked(idx % 4, 32 + (idx / 4))
-        .ok_or_else(|| EarsError::InvalidMagicPixelLocation(idx))?;
-
-    dst_pixel.0 = magic_pixel.0;
-
-    Ok(())
+    if put_data_pixel(image, idx % 4, 32 + (idx / 4), from_argb_hex(value)) {
+        Ok(())
+    } else {
+        Err(EarsError::InvalidMagicPixelLocation(idx))
+    }
 }
@@ -77,11 +77,11 @@ impl EarsFeaturesParser for EarsParserV0 {
         )?;
 
         let bitflags = read_magic_pixel!(image, 12)? & 0x00FF_FFFF;
-        if bitflags != MagicPixelsV0::Blue.get_hex() & 0x00FF_FFFF {
-            if let Some(tail) = features.tail.as_mut() {
-                tail.animate = bitflags & 1 != 0;
-                tail.swap_jacket_back = bitflags & 2 != 0;
-            }
+        if bitflags != MagicPixelsV0::Blue.get_hex() & 0x00FF_FFFF
+            && let Some(tail) = features.tail.as_mut()
+        {
+            tail.animate = bitflags & 1 != 0;
+            tail.swap_jacket_back = bitflags & 2 != 0;
         }
 
         features.emissive = read_magic_pixel!(image, 10)? == MagicPixelsV0::Orange.get_hex();
@@ -92,8 +92,8 @@ impl EarsFeaturesParser for EarsParserV0 {
     }
 
     fn detect(image: &RgbaImage) -> bool {
-        return read_magic_pixel!(image, 0)
-            .is_ok_and(|p| MagicPixelsV0::get_by_argb_hex(p) == MagicPixelsV0::Blue);
+        read_magic_pixel!(image, 0)
+            .is_ok_and(|p| MagicPixelsV0::get_by_argb_hex(p) == MagicPixelsV0::Blue)
     }
 }
 
@@ -151,7 +151,7 @@ fn write_tail_data(image: &mut RgbaImage, tail: &TailData) -> Result<()> {
             j += 1;
         }
 
-        return j + 128;
+        j + 128
     }
     write_magic_pixel(
         image,
@@ -203,10 +203,10 @@ fn write_snout_data(image: &mut RgbaImage, snout: &SnoutData) -> Result<u32> {
     let snout = (snout_width as u32) << 16 | (snout_height as u32) << 8 | snout_depth as u32;
     let etc = (snout_offset as u32) << 8;
 
-    write_raw_magic_pixel(image, 6, snout as u32)?;
-    write_raw_magic_pixel(image, 7, etc as u32)?;
+    write_raw_magic_pixel(image, 6, snout)?;
+    write_raw_magic_pixel(image, 7, etc)?;
 
-    Ok(etc as u32)
+    Ok(etc)
 }
 
 fn write_wing_data(image: &mut RgbaImage, wing: &WingData) -> Result<()> {
@@ -312,6 +312,7 @@ mod tests {
 
     #[ignore]
     #[test]
+    #[allow(clippy::manual_flatten)]
     fn v0_massive_test_works() -> Result<(), Box<dyn std::error::Error>> {
         let _ = fs::create_dir("test_images/textures_ears");
         for entry in fs::read_dir("test_images/textures")? {
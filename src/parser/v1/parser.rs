@@ -10,7 +10,7 @@ use crate::features::{
     },
 };
 use crate::parser::EarsFeaturesParser;
-use crate::parser::utils::to_argb_hex;
+use crate::parser::utils::{get_data_pixel, to_argb_hex};
 use crate::utils::bit_reader::BitReader;
 use crate::utils::errors::{EarsError, Result};
 use enum_ordinalize::Ordinalize;
@@ -68,8 +68,7 @@ impl EarsFeaturesParser for EarsParserV1 {
                     continue;
                 }
                 let c = to_argb_hex(
-                    image
-                        .get_pixel_checked(x, 32 + y)
+                    &get_data_pixel(image, x, 32 + y)
                         .ok_or(EarsError::InvalidPixelLocation(x, y))?,
                 );
 
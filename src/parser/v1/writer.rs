@@ -9,7 +9,11 @@ use crate::{
             wing::{WingAnimationMode, WingMode},
         },
     },
-    parser::{EarsFeaturesWriter, utils::from_argb_hex, v1::parser::EarsParserV1},
+    parser::{
+        EarsFeaturesWriter,
+        utils::{from_argb_hex, put_data_pixel},
+        v1::parser::EarsParserV1,
+    },
     utils::{bit_writer::BitWriter, errors::Result},
 };
 use enum_ordinalize::Ordinalize;
@@ -43,23 +47,23 @@ impl EarsWriterV1 {
             },
         )?;
 
-        if let Some(tail) = feat.tail {
-            if tail.mode != TailMode::None {
-                writer.write_long(2, (tail.segments - 1) as u64)?;
-                let [bend_0, bend_1, bend_2, bend_3] = tail.bends;
+        if let Some(tail) = feat.tail
+            && tail.mode != TailMode::None
+        {
+            writer.write_long(2, (tail.segments - 1) as u64)?;
+            let [bend_0, bend_1, bend_2, bend_3] = tail.bends;
 
-                writer.write_sam_unit(6, bend_0 / 90.0f32)?;
-                if tail.segments > 1 {
-                    writer.write_sam_unit(6, bend_1 / 90.0f32)?;
-                }
+            writer.write_sam_unit(6, bend_0 / 90.0f32)?;
+            if tail.segments > 1 {
+                writer.write_sam_unit(6, bend_1 / 90.0f32)?;
+            }
 
-                if tail.segments > 2 {
-                    writer.write_sam_unit(6, bend_2 / 90.0f32)?;
-                }
+            if tail.segments > 2 {
+                writer.write_sam_unit(6, bend_2 / 90.0f32)?;
+            }
 
-                if tail.segments > 3 {
-                    writer.write_sam_unit(6, bend_3 / 90.0f32)?;
-                }
+            if tail.segments > 3 {
+                writer.write_sam_unit(6, bend_3 / 90.0f32)?;
             }
         }
 
@@ -127,7 +131,7 @@ impl EarsFeaturesWriter for EarsWriterV1 {
 
             Self::write_features(features, &mut writer)?;
         };
-        let mut data = cursor.into_inner().into_iter();
+        let mut data = cursor.into_inner().iter().copied();
 
         for y in 0..4 {
             for x in 0..4 {
@@ -135,17 +139,16 @@ impl EarsFeaturesWriter for EarsWriterV1 {
                     0xFFEA2501
                 } else {
                     let mut c: u32 = 0xFF000000;
-                    c |= (((data.next()).as_deref().unwrap_or(&0) & 0xFF) as u32) << 16;
-                    c |= (((data.next()).as_deref().unwrap_or(&0) & 0xFF) as u32) << 8;
-                    c |= ((data.next()).as_deref().unwrap_or(&0) & 0xFF) as u32;
+                    c |= (data.next().unwrap_or(0) as u32) << 16;
+                    c |= (data.next().unwrap_or(0) as u32) << 8;
+                    c |= data.next().unwrap_or(0) as u32;
 
                     c
                 };
 
-                let pixel = image
-                    .get_pixel_mut_checked(x, 32 + y)
-                    .ok_or_else(|| crate::utils::errors::EarsError::InvalidPixelLocation(x, y))?;
-                *pixel = from_argb_hex(c);
+                if !put_data_pixel(image, x, 32 + y, from_argb_hex(c)) {
+                    return Err(crate::utils::errors::EarsError::InvalidPixelLocation(x, y));
+                }
             }
         }
 
@@ -0,0 +1,134 @@
+use image::{Rgba, RgbaImage};
+
+use crate::geometry::{BodyPart, ModelElement, Quad, feature_geometry, player_geometry};
+use crate::render::{Camera, RenderOptions, draw};
+use crate::utils::ProcessedSkin;
+use crate::utils::errors::Result;
+
+/// How an avatar looks at the head.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AvatarStyle {
+    /// The face seen straight on, unlit, with the hat layer drawn over it at the same size.
+    #[default]
+    Flat,
+    /// The head seen from above its front left corner, lit.
+    Isometric,
+}
+
+/// Renders the head of `skin` with the Ears features attached to it, like ears, horns, halos and
+/// snouts. `scale` is how many pixels one skin pixel takes, and the image is sized to fit the
+/// head and its features.
+pub fn render_avatar(skin: &ProcessedSkin, style: AvatarStyle, scale: f32) -> Result<RgbaImage> {
+    let features = skin.features.unwrap_or_default();
+
+    let mut quads: Vec<Quad> = player_geometry(features.leg_mode, skin.model)
+        .into_iter()
+        .chain(feature_geometry(&features))
+        .filter(|q| q.part == BodyPart::Head)
+        .collect();
+
+    let camera = match style {
+        AvatarStyle::Flat => {
+            flatten_hat(&mut quads);
+            Camera::front()
+        }
+        AvatarStyle::Isometric => Camera::isometric(),
+    };
+    let camera = Camera {
+        margin: 0.0,
+        ..camera
+    };
+
+    // Size the image after how big the head looks from the camera
+    let view = camera.view();
+    let mut min = [f32::MAX; 2];
+    let mut max = [f32::MIN; 2];
+    for vertex in quads.iter().flat_map(|q| q.vertices) {
+        let position = view.transform_point(vertex.position);
+        for axis in 0..2 {
+            min[axis] = min[axis].min(position[axis]);
+            max[axis] = max[axis].max(position[axis]);
+        }
+    }
+    let [width, height] =
+        [0, 1].map(|axis| ((max[axis] - min[axis]) * scale).round().max(1.0) as u32);
+
+    let options = RenderOptions {
+        width,
+        height,
+        camera,
+        shading: style == AvatarStyle::Isometric,
+        supersampling: match style {
+            AvatarStyle::Flat => 1,
+            AvatarStyle::Isometric => 2,
+        },
+        background: Rgba([0, 0, 0, 0]),
+        ..Default::default()
+    };
+    draw(skin, quads, &options)
+}
+
+/// Shrinks the hat layer to the width and height of the face, like flat avatars draw it.
+fn flatten_hat(quads: &mut [Quad]) {
+    const CENTER: [f32; 2] = [0.0, 28.0];
+    const SCALE: f32 = 8.0 / 9.0;
+
+    for quad in quads
+        .iter_mut()
+        .filter(|q| q.part == BodyPart::Head && q.element == ModelElement::Overlay)
+    {
+        for vertex in &mut quad.vertices {
+            for (position, center) in vertex.position.iter_mut().zip(CENTER) {
+                *position = center + (*position - center) * SCALE;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_avatar_is_the_face_and_hat() -> Result<()> {
+        let skin = image::open("test_images/notch_upgraded.png")
+            .unwrap()
+            .to_rgba8();
+
+        let avatar = render_avatar(&ProcessedSkin::from_image(&skin)?, AvatarStyle::Flat, 4.0)?;
+
+        assert_eq!(avatar.dimensions(), (32, 32));
+        for (x, y) in [(0, 0), (3, 5), (7, 7)] {
+            let face = *skin.get_pixel(8 + x, 8 + y);
+            let hat = *skin.get_pixel(40 + x, 8 + y);
+            let expected = if hat[3] > 0 { hat } else { face };
+            assert_eq!(*avatar.get_pixel(x * 4 + 1, y * 4 + 1), expected);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn avatars_grow_to_fit_head_features() -> Result<()> {
+        let skin = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let skin = ProcessedSkin::from_image(&skin)?;
+        let vanilla = image::open("test_images/notch_upgraded.png")
+            .unwrap()
+            .to_rgba8();
+        let vanilla = ProcessedSkin::from_image(&vanilla)?;
+
+        for style in [AvatarStyle::Flat, AvatarStyle::Isometric] {
+            let avatar = render_avatar(&skin, style, 2.0)?;
+            let plain = render_avatar(&vanilla, style, 2.0)?;
+
+            assert!(avatar.width() > plain.width());
+            assert!(avatar.pixels().any(|p| p[3] > 0));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,487 @@
+//! A CPU rasteriser for previews of a skin, Ears features included.
+//!
+//! Skins are drawn as a [`ProcessedSkin`], with emissive pixels unlit. Texels are either drawn
+//! opaque or skipped, like Minecraft's cutout rendering.
+
+mod avatar;
+mod raster;
+
+use image::{Rgba, RgbaImage};
+
+use crate::geometry::math::{Mat4, dot, normalize, sub};
+use crate::geometry::{
+    FeaturePose, ModelElement, Pose, Quad, QuadTexture, apply_pose, fit_arm_features,
+    player_geometry, posed_feature_geometry,
+};
+use crate::utils::ProcessedSkin;
+use crate::utils::errors::{EarsError, Result};
+use raster::{Framebuffer, Material, ScreenVertex, diffuse_light};
+
+pub use avatar::{AvatarStyle, render_avatar};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Orthographic,
+    /// A perspective projection with a vertical field of view in degrees.
+    Perspective {
+        fov: f32,
+    },
+}
+
+/// Where the model is seen from. The model is always centered and scaled to fit the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera {
+    /// Degrees the camera is turned around the player, 0 looking at the player's front and 90
+    /// looking at the player's left.
+    pub yaw: f32,
+    /// Degrees the camera looks down at the player.
+    pub pitch: f32,
+    pub projection: Projection,
+    /// Empty space kept around the model, as a fraction of the image size.
+    pub margin: f32,
+}
+
+impl Camera {
+    pub fn front() -> Self {
+        Self {
+            yaw: 0.0,
+            pitch: 0.0,
+            projection: Projection::Orthographic,
+            margin: 0.05,
+        }
+    }
+
+    pub fn back() -> Self {
+        Self {
+            yaw: 180.0,
+            ..Self::front()
+        }
+    }
+
+    pub fn isometric() -> Self {
+        Self {
+            yaw: 45.0,
+            pitch: 35.264,
+            ..Self::front()
+        }
+    }
+
+    fn view(&self) -> Mat4 {
+        Mat4::rotation(self.pitch, [1.0, 0.0, 0.0]) * Mat4::rotation(-self.yaw, [0.0, 1.0, 0.0])
+    }
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::front()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderOptions {
+    pub width: u32,
+    pub height: u32,
+    pub camera: Camera,
+    pub pose: Pose,
+    /// How the tail and wings are posed, at rest when `None`.
+    pub feature_pose: Option<FeaturePose>,
+    /// Whether to draw the overlay layers, like the hat and the jacket.
+    pub overlays: bool,
+    /// Whether faces are shaded depending on where they point.
+    pub shading: bool,
+    /// How many samples per pixel are taken along each axis, smoothing edges.
+    pub supersampling: u32,
+    pub background: Rgba<u8>,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            width: 256,
+            height: 512,
+            camera: Camera::default(),
+            pose: Pose::default(),
+            feature_pose: None,
+            overlays: true,
+            shading: true,
+            supersampling: 2,
+            background: Rgba([0, 0, 0, 0]),
+        }
+    }
+}
+
+/// Renders `skin` with its Ears features. Digitigrade legs are drawn with the displaced leg
+/// textures, and wings with [`ProcessedSkin::wing`], left out when it is `None`.
+pub fn render_skin(skin: &ProcessedSkin, options: &RenderOptions) -> Result<RgbaImage> {
+    let features = skin.features.unwrap_or_default();
+
+    let feature_pose = options
+        .feature_pose
+        .unwrap_or_else(|| FeaturePose::rest(&features));
+    let mut quads = player_geometry(features.leg_mode, skin.model);
+    quads.extend(posed_feature_geometry(&features, &feature_pose));
+    fit_arm_features(&mut quads, skin.model);
+    quads.retain(|q| options.overlays || q.element != ModelElement::Overlay);
+    apply_pose(&mut quads, &options.pose);
+
+    draw(skin, quads, options)
+}
+
+/// Draws `quads` in model space, textured with `skin`, as seen by the camera of `options`.
+fn draw(skin: &ProcessedSkin, mut quads: Vec<Quad>, options: &RenderOptions) -> Result<RgbaImage> {
+    let skin_material = Material {
+        texture: &skin.base,
+        emissive: skin.emissive.as_ref(),
+    };
+    let displaced_material = skin.displaced.as_ref().map(|texture| Material {
+        texture,
+        emissive: skin.displaced_emissive.as_ref(),
+    });
+    let wing_material = skin.wing.as_ref().map(|texture| Material {
+        texture,
+        emissive: None,
+    });
+    let material = |texture| match texture {
+        QuadTexture::Skin => Some(&skin_material),
+        QuadTexture::Displaced => displaced_material.as_ref(),
+        QuadTexture::Wing => wing_material.as_ref(),
+    };
+    quads.retain(|q| material(q.texture).is_some());
+
+    let factor = options.supersampling.max(1);
+    let too_large = || EarsError::InvalidRenderSize(options.width, options.height, factor);
+    let width = options.width.checked_mul(factor).ok_or_else(too_large)?;
+    let height = options.height.checked_mul(factor).ok_or_else(too_large)?;
+    let mut framebuffer = Framebuffer::new(width, height).ok_or_else(too_large)?;
+
+    let camera = &options.camera;
+    let view = camera.view();
+    for quad in &mut quads {
+        for vertex in &mut quad.vertices {
+            vertex.position = view.transform_point(vertex.position);
+        }
+        quad.normal = normalize(view.transform_vector(quad.normal));
+    }
+
+    if quads.is_empty() {
+        return Ok(framebuffer.resolve(factor, options.background));
+    }
+    let projector = Projector::fit(camera, &quads, width, height);
+
+    for quad in &quads {
+        if !projector.faces(quad.normal, quad.vertices[0].position) {
+            continue;
+        }
+
+        let material = material(quad.texture).unwrap();
+        let light = if options.shading {
+            diffuse_light(quad.normal)
+        } else {
+            1.0
+        };
+
+        let [a, b, c, d] = quad.vertices.map(|v| projector.project(v.position, v.uv));
+        framebuffer.triangle([a, b, c], material, light);
+        framebuffer.triangle([a, c, d], material, light);
+    }
+
+    Ok(framebuffer.resolve(factor, options.background))
+}
+
+/// Maps view space positions to pixels, keeping the whole model in the image.
+struct Projector {
+    center: [f32; 3],
+    half_size: [f32; 2],
+    scale: f32,
+    /// How far the camera is from the center of the model, for perspective projections.
+    distance: Option<f32>,
+}
+
+impl Projector {
+    fn fit(camera: &Camera, quads: &[Quad], width: u32, height: u32) -> Self {
+        let positions = || quads.iter().flat_map(|q| q.vertices.map(|v| v.position));
+        let mut min = [f32::MAX; 3];
+        let mut max = [f32::MIN; 3];
+        for position in positions() {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(position[axis]);
+                max[axis] = max[axis].max(position[axis]);
+            }
+        }
+
+        let center = [0, 1, 2].map(|i| (min[i] + max[i]) / 2.0);
+        let half_size = [width as f32 / 2.0, height as f32 / 2.0];
+        let usable = 1.0 - 2.0 * camera.margin;
+
+        match camera.projection {
+            Projection::Orthographic => Self {
+                center,
+                half_size,
+                scale: (half_size[0] * 2.0 * usable / (max[0] - min[0]).max(f32::EPSILON))
+                    .min(half_size[1] * 2.0 * usable / (max[1] - min[1]).max(f32::EPSILON)),
+                distance: None,
+            },
+            Projection::Perspective { fov } => {
+                let radius = positions()
+                    .map(|p| {
+                        let offset = sub(p, center);
+                        dot(offset, offset).sqrt()
+                    })
+                    .fold(0.0, f32::max);
+                let half_fov = (fov.to_radians() / 2.0).clamp(0.01, 1.5);
+
+                Self {
+                    center,
+                    half_size,
+                    scale: half_size[0].min(half_size[1]) * usable / half_fov.tan(),
+                    distance: Some(radius / half_fov.sin()),
+                }
+            }
+        }
+    }
+
+    fn project(&self, position: [f32; 3], uv: [f32; 2]) -> ScreenVertex {
+        let [x, y, z] = sub(position, self.center);
+        let (depth, inv_w) = match self.distance {
+            None => (z, 1.0),
+            Some(distance) => (1.0 / (distance - z), 1.0 / (distance - z)),
+        };
+
+        ScreenVertex {
+            x: self.half_size[0] + x * self.scale * inv_w,
+            y: self.half_size[1] - y * self.scale * inv_w,
+            depth,
+            inv_w,
+            uv,
+        }
+    }
+
+    /// Whether a face pointing at `normal` through `position` faces the camera.
+    fn faces(&self, normal: [f32; 3], position: [f32; 3]) -> bool {
+        match self.distance {
+            None => normal[2] > 0.0,
+            Some(distance) => {
+                let eye = [self.center[0], self.center[1], self.center[2] + distance];
+                dot(normal, sub(eye, position)) > 0.0
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::features::EarsFeatures;
+    use crate::features::data::tail::{TailData, TailMode};
+    use crate::layout::{JACKET_BACK, TAIL};
+    use crate::parser::{EarsFeaturesWriter, v1::writer::EarsWriterV1};
+    use crate::utils::extract_emissive_palette;
+
+    fn options(camera: Camera) -> RenderOptions {
+        RenderOptions {
+            width: 128,
+            height: 256,
+            camera,
+            overlays: false,
+            shading: false,
+            supersampling: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn front_view_shows_the_face() -> Result<()> {
+        let skin = image::open("test_images/notch_upgraded.png")
+            .unwrap()
+            .to_rgba8();
+
+        let processed = ProcessedSkin::from_image(&skin)?;
+        let render = render_skin(&processed, &options(Camera::front()))?;
+
+        assert_eq!(render.dimensions(), (128, 256));
+        // The model is 16x32 pixels, scaled by 7.2 to fit
+        assert_eq!(render.get_pixel(60, 45), skin.get_pixel(11, 12));
+        assert_eq!(render.get_pixel(0, 0)[3], 0);
+
+        let back = render_skin(&processed, &options(Camera::back()))?;
+        assert_ne!(back, render);
+
+        Ok(())
+    }
+
+    #[test]
+    fn emissive_pixels_are_drawn_unlit() -> Result<()> {
+        let skin = image::open("test_images/emissive-before.png")
+            .unwrap()
+            .to_rgba8();
+        let palette = extract_emissive_palette(&skin)?.unwrap();
+
+        let mut options = options(Camera::isometric());
+        options.shading = true;
+        let render = render_skin(&ProcessedSkin::from_image(&skin)?, &options)?;
+
+        let unlit = render
+            .pixels()
+            .filter(|p| p[3] == 255 && palette.0.contains(&image::Rgb([p[0], p[1], p[2]])));
+        assert!(unlit.count() > 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn features_are_drawn_in_every_projection() -> Result<()> {
+        let skin = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let mut skin = ProcessedSkin::from_image(&skin)?;
+        skin.wing = Some(RgbaImage::new(20, 16));
+        let vanilla = image::open("test_images/notch_upgraded.png")
+            .unwrap()
+            .to_rgba8();
+        let vanilla = ProcessedSkin::from_image(&vanilla)?;
+
+        for camera in [
+            Camera::isometric(),
+            Camera {
+                projection: Projection::Perspective { fov: 40.0 },
+                ..Camera::isometric()
+            },
+        ] {
+            let opaque = |image: &RgbaImage| image.pixels().filter(|p| p[3] > 0).count();
+            let render = render_skin(&skin, &options(camera))?;
+            let vanilla = render_skin(&vanilla, &options(camera))?;
+
+            // The ears and tail make the model bigger, so it is scaled down to fit
+            assert!(opaque(&render) > 0);
+            assert!(opaque(&render) < opaque(&vanilla));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn wings_stored_in_the_skin_are_drawn() -> Result<()> {
+        use std::io::Cursor;
+
+        use image::ImageFormat;
+
+        use crate::alfalfa::{AlfalfaData, AlfalfaDataKey, write_alfalfa};
+
+        let mut skin = image::open(
+            "test_images/ears_v0_sample_ear_out_front_claws_horn_tail_back_3_snout_4x3x4-0,2_wings_symmetric_dual_normal.png",
+        )
+        .unwrap()
+        .to_rgba8();
+        let wings = |colour| RgbaImage::from_pixel(20, 16, colour);
+        let mut png = Vec::new();
+        wings(Rgba([255, 0, 0, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let mut alfalfa = AlfalfaData::new();
+        alfalfa.set_data(AlfalfaDataKey::Wings, png);
+        write_alfalfa(&alfalfa, &mut skin)?;
+
+        let options = options(Camera::back());
+        let mut processed = ProcessedSkin::from_image(&skin)?;
+        let stored = render_skin(&processed, &options)?;
+        assert_eq!(processed.wing, Some(wings(Rgba([255, 0, 0, 255]))));
+
+        processed.wing = Some(wings(Rgba([0, 0, 255, 255])));
+        assert_ne!(stored, render_skin(&processed, &options)?);
+        processed.wing = None;
+        assert_ne!(stored, render_skin(&processed, &options)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn oversized_renders_are_rejected() -> Result<()> {
+        let skin = ProcessedSkin::from_image(&RgbaImage::new(64, 64))?;
+
+        for (width, height, supersampling) in [(u32::MAX, 16, 2), (1 << 16, 1 << 16, 1)] {
+            let options = RenderOptions {
+                width,
+                height,
+                supersampling,
+                ..Default::default()
+            };
+            assert!(matches!(
+                render_skin(&skin, &options),
+                Err(EarsError::InvalidRenderSize(w, h, s))
+                    if (w, h, s) == (width, height, supersampling)
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn digitigrade_legs_are_drawn_with_the_displaced_texture() -> Result<()> {
+        let skin = image::open("test_images/ears_v1_digitigrade_full_original.png")
+            .unwrap()
+            .to_rgba8();
+        let mut processed = ProcessedSkin::from_image(&skin)?;
+        let options = options(Camera::front());
+        let opaque = |image: &RgbaImage| image.pixels().filter(|p| p[3] > 0).count();
+
+        let render = render_skin(&processed, &options)?;
+        processed.displaced = None;
+        let without_legs = render_skin(&processed, &options)?;
+
+        // The legs are erased from the base skin, so nothing else draws them
+        assert!(opaque(&render) > opaque(&without_legs));
+
+        Ok(())
+    }
+
+    #[test]
+    fn swapped_tail_draws_like_an_unswapped_one() -> Result<()> {
+        let (tail, jacket) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        let skin = |swap_jacket_back| -> Result<RgbaImage> {
+            let mut skin = image::open("test_images/notch_upgraded.png")
+                .unwrap()
+                .to_rgba8();
+            let features = EarsFeatures {
+                tail: Some(TailData {
+                    mode: TailMode::Down,
+                    segments: 1,
+                    swap_jacket_back,
+                    ..TailData::default()
+                }),
+                ..EarsFeatures::default()
+            };
+            EarsWriterV1::write(&mut skin, &features)?;
+
+            // A swapped tail is stored in the jacket back, and the jacket back in the tail
+            let (stored_tail, stored_jacket) = if swap_jacket_back {
+                (jacket, tail)
+            } else {
+                (tail, jacket)
+            };
+            for (x, y) in TAIL.pixels() {
+                skin.put_pixel(x, y, stored_tail);
+            }
+            for (x, y) in JACKET_BACK.pixels() {
+                skin.put_pixel(x, y, stored_jacket);
+            }
+            render_skin(
+                &ProcessedSkin::from_image(&skin)?,
+                &RenderOptions {
+                    overlays: true,
+                    ..options(Camera::back())
+                },
+            )
+        };
+
+        let swapped = skin(true)?;
+
+        assert_eq!(swapped, skin(false)?);
+        assert!(swapped.pixels().any(|p| *p == tail));
+        assert!(swapped.pixels().any(|p| *p == jacket));
+
+        Ok(())
+    }
+}
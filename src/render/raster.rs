@@ -0,0 +1,146 @@
+use image::{Rgba, RgbaImage};
+
+use crate::geometry::math::dot;
+
+/// Alpha below which a texel is treated as fully transparent, like Minecraft's cutout rendering.
+const ALPHA_CUTOFF: u8 = 26;
+
+/// A vertex projected onto the screen.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ScreenVertex {
+    /// Position in pixels, `y` going down.
+    pub(crate) x: f32,
+    pub(crate) y: f32,
+    /// Bigger is closer to the camera.
+    pub(crate) depth: f32,
+    /// `1 / w`, for perspective-correct texturing.
+    pub(crate) inv_w: f32,
+    pub(crate) uv: [f32; 2],
+}
+
+/// The textures a triangle samples, the emissive one being drawn unlit over the other.
+pub(crate) struct Material<'a> {
+    pub(crate) texture: &'a RgbaImage,
+    pub(crate) emissive: Option<&'a RgbaImage>,
+}
+
+pub(crate) struct Framebuffer {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    color: Vec<[f32; 4]>,
+    depth: Vec<f32>,
+}
+
+impl Framebuffer {
+    /// Returns `None` if the framebuffer has more pixels than a `u32` can count.
+    pub(crate) fn new(width: u32, height: u32) -> Option<Self> {
+        let size = width.checked_mul(height)? as usize;
+        Some(Self {
+            width,
+            height,
+            color: vec![[0.0; 4]; size],
+            depth: vec![f32::NEG_INFINITY; size],
+        })
+    }
+
+    /// Draws a triangle shaded by `light`, skipping texels that are transparent.
+    pub(crate) fn triangle(
+        &mut self,
+        [a, b, c]: [ScreenVertex; 3],
+        material: &Material,
+        light: f32,
+    ) {
+        let area = edge(a, b, c.x, c.y);
+        if area.abs() < f32::EPSILON {
+            return;
+        }
+
+        let min_x = a.x.min(b.x).min(c.x).floor().max(0.0) as u32;
+        let min_y = a.y.min(b.y).min(c.y).floor().max(0.0) as u32;
+        let max_x = (a.x.max(b.x).max(c.x).ceil() as u32).min(self.width);
+        let max_y = (a.y.max(b.y).max(c.y).ceil() as u32).min(self.height);
+
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                let w =
+                    [edge(b, c, px, py), edge(c, a, px, py), edge(a, b, px, py)].map(|e| e / area);
+                if w.iter().any(|&w| w < 0.0) {
+                    continue;
+                }
+
+                let index = (y * self.width + x) as usize;
+                let depth = w[0] * a.depth + w[1] * b.depth + w[2] * c.depth;
+                if depth <= self.depth[index] {
+                    continue;
+                }
+
+                let inv_w = w[0] * a.inv_w + w[1] * b.inv_w + w[2] * c.inv_w;
+                let uv = [0, 1].map(|i| {
+                    (w[0] * a.uv[i] * a.inv_w + w[1] * b.uv[i] * b.inv_w + w[2] * c.uv[i] * c.inv_w)
+                        / inv_w
+                });
+
+                let emissive = material
+                    .emissive
+                    .map(|e| sample(e, uv))
+                    .filter(|texel| texel[3] >= ALPHA_CUTOFF);
+                let color = match emissive {
+                    Some(Rgba([r, g, b, _])) => [r, g, b].map(|c| c as f32 / 255.0),
+                    None => {
+                        let texel = sample(material.texture, uv);
+                        if texel[3] < ALPHA_CUTOFF {
+                            continue;
+                        }
+                        [texel[0], texel[1], texel[2]].map(|c| c as f32 / 255.0 * light)
+                    }
+                };
+
+                self.depth[index] = depth;
+                self.color[index] = [color[0], color[1], color[2], 1.0];
+            }
+        }
+    }
+
+    /// Averages every `factor` x `factor` block of pixels over `background`.
+    pub(crate) fn resolve(&self, factor: u32, background: Rgba<u8>) -> RgbaImage {
+        let background = background.0.map(|c| c as f32 / 255.0);
+        let samples = (factor * factor) as f32;
+
+        RgbaImage::from_fn(self.width / factor, self.height / factor, |x, y| {
+            let mut sum = [0.0; 4];
+            for sy in 0..factor {
+                for sx in 0..factor {
+                    let index = ((y * factor + sy) * self.width + x * factor + sx) as usize;
+                    let color = self.color[index];
+                    let pixel = if color[3] > 0.0 { color } else { background };
+                    // Premultiplied, so transparent samples don't darken the edges
+                    for i in 0..3 {
+                        sum[i] += pixel[i] * pixel[3];
+                    }
+                    sum[3] += pixel[3];
+                }
+            }
+
+            let alpha = sum[3] / samples;
+            let color = [0, 1, 2].map(|i| if sum[3] > 0.0 { sum[i] / sum[3] } else { 0.0 });
+            Rgba([color[0], color[1], color[2], alpha].map(|c| (c * 255.0).round() as u8))
+        })
+    }
+}
+
+fn edge(a: ScreenVertex, b: ScreenVertex, x: f32, y: f32) -> f32 {
+    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x)
+}
+
+fn sample(texture: &RgbaImage, [u, v]: [f32; 2]) -> Rgba<u8> {
+    let x = ((u * texture.width() as f32) as u32).min(texture.width() - 1);
+    let y = ((v * texture.height() as f32) as u32).min(texture.height() - 1);
+    *texture.get_pixel(x, y)
+}
+
+/// How lit a face pointing at `normal` is, in view space.
+pub(crate) fn diffuse_light(normal: [f32; 3]) -> f32 {
+    const LIGHT: [f32; 3] = [0.24, 0.49, 0.84];
+    0.55 + 0.45 * dot(normal, LIGHT).max(0.0)
+}
@@ -1,109 +1,102 @@
 use image::RgbaImage;
 
 use crate::features::{EarsFeatures, data::leg::LegMode};
+use crate::layout::{BodyPart, Face, Layer, TextureRegion, face_region, layer_regions};
+use crate::utils::SkinModel;
 
-#[derive(Clone, Copy, Debug)]
-pub(crate) struct Rectangle {
-    pub x1: u32,
-    pub y1: u32,
-    pub x2: u32,
-    pub y2: u32,
-    pub force_opaque: bool,
-}
+/// Returns the parts of the skin that are forced opaque, leaving out the parts of the legs that
+/// are displaced by `leg_mode`. The tops of the legs are hidden in the body, so they stay.
+///
+/// With partial digitigrade legs the left leg keeps the top half of its sides, while the right leg
+/// keeps rows 20 to 38 as it always has.
+pub(crate) fn forced_opaque_regions(
+    leg_mode: Option<LegMode>,
+    model: SkinModel,
+) -> Vec<TextureRegion> {
+    let mut regions = Vec::new();
+    for part in BodyPart::ALL {
+        let [top_and_bottom, sides] = layer_regions(part, Layer::Base, model);
+        let top = face_region(part, Layer::Base, Face::Top, model);
+        let is_leg = matches!(part, BodyPart::LeftLeg | BodyPart::RightLeg);
 
-const fn rectangle(x: u32, y: u32, width: u32, height: u32, force_opaque: bool) -> Rectangle {
-    Rectangle {
-        x1: x,
-        y1: y,
-        x2: x + width,
-        y2: y + height,
-        force_opaque,
+        match leg_mode {
+            Some(LegMode::DigitigradeFull) if is_leg => regions.push(top),
+            Some(LegMode::DigitigradePartial) if is_leg => {
+                let height = match part {
+                    BodyPart::RightLeg => 18,
+                    _ => sides.height / 2,
+                };
+                regions.extend([
+                    top,
+                    TextureRegion::new(sides.x, sides.y, sides.width, height),
+                ]);
+            }
+            _ => regions.extend([top_and_bottom, sides]),
+        }
     }
+    regions
 }
 
-pub(crate) const FORCED_OPAQUE_REGIONS: &[Rectangle] = &[
-    rectangle(8, 0, 16, 8, false),
-    rectangle(0, 8, 32, 8, false),
-    rectangle(4, 16, 8, 4, false),
-    rectangle(20, 16, 16, 4, false),
-    rectangle(44, 16, 8, 4, false),
-    rectangle(0, 20, 56, 12, false),
-    rectangle(20, 48, 8, 4, false),
-    rectangle(36, 48, 8, 4, false),
-    rectangle(16, 52, 32, 12, false),
-];
-
-pub(crate) const LEG_BOTTOM_HALF_REGIONS: &[Rectangle] = &[
-    rectangle(24, 48, 4, 4, true),  // left leg bottom
-    rectangle(16, 58, 16, 6, true), // left leg
-    rectangle(8, 16, 4, 4, true),   // right leg bottom
-    rectangle(0, 26, 16, 6, true),  // right leg
-    rectangle(8, 32, 4, 4, false),  // right leg pant bottom
-    rectangle(0, 42, 16, 6, false), // right leg pant
-    rectangle(8, 48, 4, 4, false),  // left leg pant bottom
-    rectangle(0, 58, 16, 6, false), // left leg pant
-];
-
-/// A list of the rectangles that are displaced if full digitigrade legs are enabled.
-pub(crate) const LEG_REGIONS: &[Rectangle] = &[
-    rectangle(20, 48, 8, 4, true),   // left leg pole
-    rectangle(16, 52, 16, 12, true), // left leg
-    rectangle(4, 16, 8, 4, true),    // right leg pole
-    rectangle(0, 20, 16, 12, true),  // right leg
-    rectangle(4, 32, 8, 4, false),   // right leg pant pole
-    rectangle(0, 36, 16, 12, false), // right leg pant
-    rectangle(4, 48, 8, 4, false),   // left leg pant pole
-    rectangle(0, 52, 16, 12, false), // left leg pant
-];
-
-const FORCED_OPAQUE_REGIONS_WITHOUT_LEG_BOTTOM_REGIONS: &[Rectangle] = &[
-    rectangle(8, 0, 16, 8, false),
-    rectangle(0, 8, 32, 8, false),
-    rectangle(4, 16, 4, 4, false),
-    rectangle(20, 16, 16, 4, false),
-    rectangle(44, 16, 8, 4, false),
-    rectangle(0, 20, 16, 18, false),
-    rectangle(16, 20, 40, 12, false),
-    rectangle(20, 48, 4, 4, false),
-    rectangle(36, 48, 8, 4, false),
-    rectangle(16, 52, 16, 6, false),
-    rectangle(32, 52, 16, 12, false),
-];
-
-const FORCED_OPAQUE_REGIONS_WITHOUT_LEG_REGIONS: &[Rectangle] = &[
-    rectangle(8, 0, 16, 8, false),
-    rectangle(0, 8, 32, 8, false),
-    rectangle(4, 16, 4, 4, false),
-    rectangle(20, 16, 16, 4, false),
-    rectangle(44, 16, 8, 4, false),
-    rectangle(16, 20, 40, 12, false),
-    rectangle(20, 48, 4, 4, false),
-    rectangle(36, 48, 8, 4, false),
-    rectangle(32, 52, 16, 12, false),
-];
+/// Returns the parts of the leg textures that are displaced by `leg_mode`, and whether they are
+/// forced opaque.
+pub(crate) fn displaced_leg_regions(leg_mode: LegMode) -> Vec<(TextureRegion, bool)> {
+    let mut regions = Vec::new();
+    for part in [BodyPart::LeftLeg, BodyPart::RightLeg] {
+        for layer in Layer::ALL {
+            let force_opaque = layer == Layer::Base;
+            let [top_and_bottom, sides] = layer_regions(part, layer, SkinModel::Classic);
+
+            match leg_mode {
+                LegMode::Plantigrade => {}
+                LegMode::DigitigradePartial => {
+                    let bottom = face_region(part, layer, Face::Bottom, SkinModel::Classic);
+                    let half = sides.height / 2;
+                    regions.extend([
+                        (bottom, force_opaque),
+                        (
+                            TextureRegion::new(sides.x, sides.y + half, sides.width, half),
+                            force_opaque,
+                        ),
+                    ]);
+                }
+                LegMode::DigitigradeFull => {
+                    regions.extend([(top_and_bottom, force_opaque), (sides, force_opaque)])
+                }
+            }
+        }
+    }
+    regions
+}
 
 pub fn strip_alpha(image: &mut RgbaImage) {
     strip_alpha_for_features(image, None);
 }
 
+/// Strips alpha from the parts of a classic skin that are forced opaque. Use
+/// [`strip_alpha_for_model`] for skins with slim arms.
 pub fn strip_alpha_for_features(image: &mut RgbaImage, features: Option<&EarsFeatures>) {
+    strip_alpha_for_model(image, features, SkinModel::Classic);
+}
+
+/// Strips alpha from the parts of the skin that are forced opaque, leaving the texture slim arms
+/// don't use as it is.
+pub fn strip_alpha_for_model(
+    image: &mut RgbaImage,
+    features: Option<&EarsFeatures>,
+    model: SkinModel,
+) {
     let leg_mode = features.map(|features| features.leg_mode);
-    let regions = match leg_mode {
-        Some(LegMode::DigitigradePartial) => FORCED_OPAQUE_REGIONS_WITHOUT_LEG_BOTTOM_REGIONS,
-        Some(LegMode::DigitigradeFull) => FORCED_OPAQUE_REGIONS_WITHOUT_LEG_REGIONS,
-        _ => FORCED_OPAQUE_REGIONS,
-    };
-    strip_alpha_regions(image, regions);
+    strip_alpha_regions(image, &forced_opaque_regions(leg_mode, model));
 }
 
-fn strip_alpha_regions(image: &mut RgbaImage, regions: &[Rectangle]) {
+fn strip_alpha_regions(image: &mut RgbaImage, regions: &[TextureRegion]) {
     let x_scale = image.width() as f32 / 64.0;
     let y_scale = image.height() as f32 / 64.0;
     for region in regions {
-        let x1 = (region.x1 as f32 * x_scale) as u32;
-        let y1 = (region.y1 as f32 * y_scale) as u32;
-        let x2 = (region.x2 as f32 * x_scale) as u32;
-        let y2 = (region.y2 as f32 * y_scale) as u32;
+        let x1 = (region.x as f32 * x_scale) as u32;
+        let y1 = (region.y as f32 * y_scale) as u32;
+        let x2 = ((region.x + region.width) as f32 * x_scale) as u32;
+        let y2 = ((region.y + region.height) as f32 * y_scale) as u32;
         for y in y1..y2 {
             for x in x1..x2 {
                 if let Some(pixel) = image.get_pixel_mut_checked(x, y) {
@@ -116,7 +109,12 @@ fn strip_alpha_regions(image: &mut RgbaImage, regions: &[Rectangle]) {
 
 #[cfg(test)]
 mod tests {
-    use crate::utils::alpha::{strip_alpha};
+    use image::Rgba;
+
+    use crate::features::data::leg::LegMode;
+    use crate::layout::TextureRegion;
+    use crate::utils::SkinModel;
+    use crate::utils::alpha::{forced_opaque_regions, strip_alpha, strip_alpha_for_model};
 
     #[test]
     fn alpha_stripper_works() {
@@ -148,4 +146,56 @@ mod tests {
             "test_images/notch_upgraded_alpha_stripped_hd.png",
         );
     }
+
+    #[test]
+    fn alpha_stripper_keeps_slim_arm_padding() {
+        let mut image = image::RgbaImage::new(64, 64);
+        strip_alpha_for_model(&mut image, None, SkinModel::Slim);
+
+        for (x, y) in [(50, 16), (55, 31), (43, 51), (46, 52)] {
+            assert_eq!(*image.get_pixel(x, y), Rgba([0, 0, 0, 0]), "({x}, {y})");
+        }
+        for (x, y) in [(49, 16), (53, 31), (41, 51), (45, 52)] {
+            assert_eq!(image.get_pixel(x, y)[3], u8::MAX, "({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn forced_opaque_regions_leave_out_displaced_legs() {
+        let plantigrade = forced_opaque_regions(None, SkinModel::Classic);
+        let legs = |leg_mode| {
+            let regions = forced_opaque_regions(Some(leg_mode), SkinModel::Classic);
+            // The head, torso and arms come first
+            assert_eq!(regions[..8], plantigrade[..8], "{leg_mode:?}");
+            regions[8..].to_vec()
+        };
+
+        assert_eq!(legs(LegMode::Plantigrade), plantigrade[8..]);
+        assert_eq!(
+            legs(LegMode::Plantigrade),
+            [
+                TextureRegion::new(20, 48, 8, 4),
+                TextureRegion::new(16, 52, 16, 12),
+                TextureRegion::new(4, 16, 8, 4),
+                TextureRegion::new(0, 20, 16, 12),
+            ]
+        );
+        // The top half of the left leg's sides, and 18 rows of the right leg's.
+        assert_eq!(
+            legs(LegMode::DigitigradePartial),
+            [
+                TextureRegion::new(20, 48, 4, 4),
+                TextureRegion::new(16, 52, 16, 6),
+                TextureRegion::new(4, 16, 4, 4),
+                TextureRegion::new(0, 20, 16, 18),
+            ]
+        );
+        assert_eq!(
+            legs(LegMode::DigitigradeFull),
+            [
+                TextureRegion::new(20, 48, 4, 4),
+                TextureRegion::new(4, 16, 4, 4),
+            ]
+        );
+    }
 }
@@ -1,34 +1,156 @@
 use image::{
     RgbaImage,
-    imageops::{self, crop_imm},
+    imageops::{self, FilterType, crop_imm},
 };
 
+use crate::utils::errors::{EarsError, Result};
+
+/// What to do with HD Mojang capes, which are bigger than Ears capes can be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum HdCapeHandling {
+    /// Fail with [`EarsError::UnsupportedHdCape`].
+    #[default]
+    Reject,
+    /// Scale the cape down to the normal size, keeping one pixel of every block.
+    Downscale,
+    /// Keep the cape's resolution, making an Ears cape of the same multiple of 20x16.
+    Keep,
+}
+
+/// Converts an Ears cape to the 64x32 layout Mojang capes use.
+///
+/// Ears capes are 20x16, or an integer multiple of it for HD capes, which become the same
+/// multiple of 64x32. Capes that already use Mojang's layout are returned as they are.
 pub fn convert_ears_cape_to_mojang_cape(ears_cape: RgbaImage) -> RgbaImage {
-    if (ears_cape.width() == 64) && (ears_cape.height() == 32) {
+    let (width, height) = ears_cape.dimensions();
+    if (width % 64 == 0) && (width == height * 2) {
         return ears_cape;
     }
 
-    let mut final_cape = RgbaImage::new(64, 32);
+    let scale = (width / 20).max(1);
+    let mut final_cape = RgbaImage::new(64 * scale, 32 * scale);
 
-    let front_view = crop_imm(&ears_cape, 0, 0, 20, 16);
-    let back_view = crop_imm(&ears_cape, 10, 0, 20, 16);
-    let left_view = crop_imm(&ears_cape, 0, 0, 1, 16);
-    let right_view = crop_imm(&ears_cape, 9, 0, 1, 16);
-    let top = crop_imm(&ears_cape, 0, 0, 10, 1);
-    let bottom = crop_imm(&ears_cape, 10, 15, 10, 1);
+    let mut copy = |(x, y, width, height), (to_x, to_y)| {
+        let view = crop_imm(
+            &ears_cape,
+            x * scale,
+            y * scale,
+            width * scale,
+            height * scale,
+        );
+        imageops::replace(
+            &mut final_cape,
+            &*view,
+            (to_x * scale) as i64,
+            (to_y * scale) as i64,
+        );
+    };
 
-    imageops::replace(&mut final_cape, &*front_view, 1, 1);
-    imageops::replace(&mut final_cape, &*back_view, 12, 1);
+    // Front and back view
+    copy((0, 0, 20, 16), (1, 1));
+    copy((10, 0, 20, 16), (12, 1));
 
-    imageops::replace(&mut final_cape, &*left_view, 0, 1);
-    imageops::replace(&mut final_cape, &*right_view, 11, 1);
+    // Left and right view
+    copy((0, 0, 1, 16), (0, 1));
+    copy((9, 0, 1, 16), (11, 1));
 
-    imageops::replace(&mut final_cape, &*top, 1, 0);
-    imageops::replace(&mut final_cape, &*bottom, 11, 0);
+    // Top and bottom
+    copy((0, 0, 10, 1), (1, 0));
+    copy((10, 15, 10, 1), (11, 0));
 
     final_cape
 }
 
+/// Draws the elytra part of `mojang_cape` from its cape art, like the game would show a cape on
+/// elytra: the back of the cape is stretched over the outside of each wing, the inside of the cape
+/// over the inside of each wing, and its edges over the edges of the wings.
+///
+/// Capes that already have elytra art are left as they are. HD capes are supported.
+pub fn generate_elytra(mojang_cape: &mut RgbaImage) {
+    let scale = (mojang_cape.width() / 64).max(1);
+
+    let has_elytra = crop_imm(mojang_cape, 22 * scale, 0, 24 * scale, 22 * scale)
+        .to_image()
+        .pixels()
+        .any(|pixel| pixel[3] != 0);
+    if has_elytra {
+        return;
+    }
+
+    type Rect = (u32, u32, u32, u32);
+    const STRETCHES: [(Rect, Rect); 6] = [
+        // Back of the cape over the outside of the wing, inside over the inside
+        ((1, 1, 10, 16), (36, 2, 10, 20)),
+        ((12, 1, 10, 16), (24, 2, 10, 20)),
+        // Left and right edges
+        ((0, 1, 1, 16), (22, 2, 2, 20)),
+        ((11, 1, 1, 16), (34, 2, 2, 20)),
+        // Top and bottom
+        ((1, 0, 10, 1), (24, 0, 10, 2)),
+        ((11, 0, 10, 1), (34, 0, 10, 2)),
+    ];
+    for ((x, y, width, height), (to_x, to_y, to_width, to_height)) in STRETCHES {
+        let view = crop_imm(
+            mojang_cape,
+            x * scale,
+            y * scale,
+            width * scale,
+            height * scale,
+        )
+        .to_image();
+        let stretched = imageops::resize(
+            &view,
+            to_width * scale,
+            to_height * scale,
+            FilterType::Nearest,
+        );
+        imageops::replace(
+            mojang_cape,
+            &stretched,
+            (to_x * scale) as i64,
+            (to_y * scale) as i64,
+        );
+    }
+}
+
+/// Converts a 64x32 Mojang cape to the 20x16 layout Ears stores capes in, the inverse of
+/// [`convert_ears_cape_to_mojang_cape`].
+///
+/// The edges of the cape are left out, as Ears draws them from the edges of the front and back,
+/// and so is the elytra. HD capes, whose size is a multiple of 64x32, are handled as asked by `hd`.
+pub fn convert_mojang_cape_to_ears_cape(
+    mojang_cape: RgbaImage,
+    hd: HdCapeHandling,
+) -> Result<RgbaImage> {
+    let (width, height) = mojang_cape.dimensions();
+    if (width % 20 == 0) && (width * 4 == height * 5) {
+        return Ok(mojang_cape);
+    }
+    if width < 64 || width % 64 != 0 || width != height * 2 {
+        return Err(EarsError::InvalidCapeSize(width, height));
+    }
+
+    let (mojang_cape, scale) = match (width / 64, hd) {
+        (1, _) => (mojang_cape, 1),
+        (_, HdCapeHandling::Reject) => return Err(EarsError::UnsupportedHdCape(width, height)),
+        (_, HdCapeHandling::Downscale) => (
+            imageops::resize(&mojang_cape, 64, 32, FilterType::Nearest),
+            1,
+        ),
+        (scale, HdCapeHandling::Keep) => (mojang_cape, scale),
+    };
+
+    let mut ears_cape = RgbaImage::new(20 * scale, 16 * scale);
+
+    let front_view = crop_imm(&mojang_cape, scale, scale, 10 * scale, 16 * scale);
+    let back_view = crop_imm(&mojang_cape, 12 * scale, scale, 10 * scale, 16 * scale);
+
+    imageops::replace(&mut ears_cape, &*front_view, 0, 0);
+    imageops::replace(&mut ears_cape, &*back_view, (10 * scale) as i64, 0);
+
+    Ok(ears_cape)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -45,4 +167,98 @@ mod test {
 
         assert_eq!(new_image, expected_image);
     }
+
+    #[test]
+    fn cape_format_conversion_roundtrips() -> Result<()> {
+        let image = image::open("test_images/creeper_cape_ears.png")
+            .unwrap()
+            .to_rgba8();
+
+        let mojang_cape = convert_ears_cape_to_mojang_cape(image.clone());
+        let ears_cape = convert_mojang_cape_to_ears_cape(mojang_cape, HdCapeHandling::Reject)?;
+
+        assert_eq!(ears_cape, image);
+
+        Ok(())
+    }
+
+    #[test]
+    fn hd_capes_are_rejected_or_downscaled() -> Result<()> {
+        let image = image::open("test_images/creeper_cape_ears.png")
+            .unwrap()
+            .to_rgba8();
+        let mojang_cape = convert_ears_cape_to_mojang_cape(image.clone());
+        let hd_cape = imageops::resize(&mojang_cape, 128, 64, FilterType::Nearest);
+
+        assert!(matches!(
+            convert_mojang_cape_to_ears_cape(hd_cape.clone(), HdCapeHandling::Reject),
+            Err(EarsError::UnsupportedHdCape(128, 64))
+        ));
+        assert_eq!(
+            convert_mojang_cape_to_ears_cape(hd_cape, HdCapeHandling::Downscale)?,
+            image
+        );
+        assert!(matches!(
+            convert_mojang_cape_to_ears_cape(RgbaImage::new(64, 64), HdCapeHandling::Downscale),
+            Err(EarsError::InvalidCapeSize(64, 64))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn hd_cape_format_conversion_works() -> Result<()> {
+        let image = image::open("test_images/creeper_cape_ears.png")
+            .unwrap()
+            .to_rgba8();
+        let hd_image = imageops::resize(&image, 40, 32, FilterType::Nearest);
+        let expected_image = image::open("test_images/creeper_cape_ears_processed.png")
+            .unwrap()
+            .to_rgba8();
+
+        let mojang_cape = convert_ears_cape_to_mojang_cape(hd_image.clone());
+        assert_eq!(
+            mojang_cape,
+            imageops::resize(&expected_image, 128, 64, FilterType::Nearest)
+        );
+
+        let ears_cape = convert_mojang_cape_to_ears_cape(mojang_cape, HdCapeHandling::Keep)?;
+        assert_eq!(ears_cape, hd_image);
+
+        Ok(())
+    }
+
+    #[test]
+    fn elytra_is_drawn_from_the_cape() {
+        let image = image::open("test_images/creeper_cape_ears.png")
+            .unwrap()
+            .to_rgba8();
+
+        for scale in [1, 2] {
+            let ears_cape = imageops::resize(&image, 20 * scale, 16 * scale, FilterType::Nearest);
+            let mut mojang_cape = convert_ears_cape_to_mojang_cape(ears_cape);
+            let cape = crop_imm(&mojang_cape, 0, 0, 22 * scale, 17 * scale).to_image();
+
+            generate_elytra(&mut mojang_cape);
+
+            // The cape is untouched, and the outside of the wing starts like the cape's back
+            assert_eq!(
+                crop_imm(&mojang_cape, 0, 0, 22 * scale, 17 * scale).to_image(),
+                cape
+            );
+            assert_eq!(
+                mojang_cape.get_pixel(36 * scale, 2 * scale),
+                cape.get_pixel(scale, scale)
+            );
+            assert_eq!(
+                mojang_cape.get_pixel(46 * scale - 1, 22 * scale - 1),
+                cape.get_pixel(11 * scale - 1, 17 * scale - 1)
+            );
+
+            // Elytra art is never overwritten
+            let elytra = mojang_cape.clone();
+            generate_elytra(&mut mojang_cape);
+            assert_eq!(mojang_cape, elytra);
+        }
+    }
 }
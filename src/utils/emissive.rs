@@ -1,12 +1,20 @@
-use image::{Rgb, Rgba, RgbaImage};
+use image::{Pixel, Rgb, Rgba, RgbaImage};
 
-use crate::{parser::EarsParser, utils::errors::Result};
+use crate::layout::{DATA_BLOCK, EMISSIVE_PALETTE, skin_scale};
+use crate::parser::EarsParser;
+use crate::parser::utils::{get_data_pixel, put_data_pixel};
+use crate::utils::errors::{EarsError, Result};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct EarsEmissivePalette(pub Vec<Rgb<u8>>);
 
 pub const MAX_EMISSIVE_COLORS: usize = 16;
 
+/// A 64x64 mask of the skin pixels that should glow, indexed as `mask[y][x]` like an
+/// [`EraseMask`](crate::alfalfa::utils::EraseMask). On HD skins every entry covers the matching
+/// scaled block of pixels.
+pub type EmissiveMask = [[bool; 64]; 64];
+
 impl From<Vec<Rgb<u8>>> for EarsEmissivePalette {
     fn from(palette: Vec<Rgb<u8>>) -> Self {
         Self(palette)
@@ -20,7 +28,7 @@ impl From<EarsEmissivePalette> for Vec<Rgb<u8>> {
 }
 
 pub fn extract_emissive_palette(skin: &RgbaImage) -> Result<Option<EarsEmissivePalette>> {
-    if EarsParser::parse(&skin)?.filter(|f| f.emissive).is_none() {
+    if EarsParser::parse(skin)?.filter(|f| f.emissive).is_none() {
         return Ok(None);
     }
 
@@ -28,7 +36,9 @@ pub fn extract_emissive_palette(skin: &RgbaImage) -> Result<Option<EarsEmissiveP
 
     for x in 52..56 {
         for y in 32..36 {
-            let color = skin.get_pixel(x, y);
+            let Some(color) = get_data_pixel(skin, x, y) else {
+                continue;
+            };
             if color.0[3] /* alpha */ > 0 {
                 emissive_palette.push(Rgb([color.0[0], color.0[1], color.0[2]]));
             }
@@ -51,13 +61,10 @@ pub fn write_emissive_palette(
     let mut idx = 0;
     for x in 52..56 {
         for y in 32..36 {
-            if let Some(px) = skin.get_pixel_mut_checked(x, y) {
-                let pixel = pixels
-                    .get(idx)
-                    .map_or(Rgba([0, 0, 0, 0]), |&Rgb([r, g, b])| Rgba([r, g, b, 255]));
-
-                *px = pixel;
-            }
+            let pixel = pixels
+                .get(idx)
+                .map_or(Rgba([0, 0, 0, 0]), |&Rgb([r, g, b])| Rgba([r, g, b, 255]));
+            put_data_pixel(skin, x, y, pixel);
 
             idx += 1;
         }
@@ -84,16 +91,87 @@ pub fn apply_emissive_palette(
     Ok(emissive_texture)
 }
 
+/// Picks the emissive palette that makes exactly the pixels of `skin` in `mask` glow.
+///
+/// Pixels that don't glow but share a colour with the palette are nudged by one unit, or two for
+/// colours at the edge of the RGB range whose every neighbour is in the palette, so that
+/// [`apply_emissive_palette`] leaves them alone. Transparent pixels never glow, and the feature
+/// data and emissive palette blocks are left as they are, since writing them replaces them
+/// anyway. Returns an error if the glowing pixels have more than [`MAX_EMISSIVE_COLORS`] colours.
+pub fn generate_emissive_palette(
+    skin: &mut RgbaImage,
+    mask: &EmissiveMask,
+) -> Result<EarsEmissivePalette> {
+    let Some(scale) = skin_scale(skin) else {
+        return Err(EarsError::InvalidSkinSize(skin.width(), skin.height()));
+    };
+
+    let blocks = [DATA_BLOCK.scaled(scale), EMISSIVE_PALETTE.scaled(scale)];
+    let pixels: Vec<(u32, u32, bool)> = skin
+        .enumerate_pixels()
+        .filter(|(x, y, pixel)| pixel[3] > 0 && !blocks.iter().any(|b| b.contains(*x, *y)))
+        .map(|(x, y, _)| (x, y, mask[(y / scale) as usize][(x / scale) as usize]))
+        .collect();
+
+    let mut palette: Vec<Rgb<u8>> = Vec::new();
+    for &(x, y, _) in pixels.iter().filter(|(_, _, glows)| *glows) {
+        let colour = skin.get_pixel(x, y).to_rgb();
+        if !palette.contains(&colour) {
+            palette.push(colour);
+        }
+    }
+    if palette.len() > MAX_EMISSIVE_COLORS {
+        return Err(EarsError::TooManyEmissiveColors(palette.len()));
+    }
+
+    for &(x, y, _) in pixels.iter().filter(|(_, _, glows)| !*glows) {
+        let pixel = skin.get_pixel_mut(x, y);
+        if palette.contains(&pixel.to_rgb()) {
+            let Rgb([r, g, b]) = nudge(pixel.to_rgb(), &palette);
+            pixel.0 = [r, g, b, pixel[3]];
+        }
+    }
+
+    Ok(EarsEmissivePalette(palette))
+}
+
+/// Returns the closest colour to `colour` that isn't in `palette`, changing blue first.
+fn nudge(colour: Rgb<u8>, palette: &[Rgb<u8>]) -> Rgb<u8> {
+    // Even in a corner of the RGB cube, 26 colours are at most two units away, more than the
+    // other 15 colours of a full palette can take
+    let steps = [0, -1, 1, -2, 2];
+    steps
+        .into_iter()
+        .flat_map(|dr| {
+            steps
+                .into_iter()
+                .flat_map(move |dg| steps.into_iter().map(move |db| [dr, dg, db]))
+        })
+        .filter_map(|offset: [i16; 3]| {
+            let mut nudged = colour;
+            for (channel, offset) in nudged.0.iter_mut().zip(offset) {
+                *channel = u8::try_from(*channel as i16 + offset).ok()?;
+            }
+            let distance = offset.iter().map(|offset| offset.abs()).max();
+            Some((nudged, distance))
+        })
+        .filter(|(nudged, _)| *nudged != colour && !palette.contains(nudged))
+        .min_by_key(|&(_, distance)| distance)
+        .map(|(nudged, _)| nudged)
+        .expect("a palette can't take every colour two units around one of its colours")
+}
+
 #[cfg(test)]
 mod tests {
-    use image::RgbaImage;
+    use image::{Rgb, RgbaImage};
 
+    use super::{MAX_EMISSIVE_COLORS, nudge};
     use crate::{
         features::EarsFeatures,
         parser::{EarsFeaturesWriter, v0::writer::EarsWriterV0},
         utils::{
-            apply_emissive_palette, errors::Result, extract_emissive_palette,
-            write_emissive_palette,
+            apply_emissive_palette, errors::EarsError, errors::Result, extract_emissive_palette,
+            generate_emissive_palette, rescale_skin, write_emissive_palette,
         },
     };
 
@@ -160,4 +238,85 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn generated_palette_makes_the_mask_glow() -> Result<()> {
+        let mut image = image::open("test_images/notch_upgraded.png")
+            .unwrap()
+            .to_rgba8();
+        EarsWriterV0::write(
+            &mut image,
+            &EarsFeatures {
+                emissive: true,
+                ..Default::default()
+            },
+        )?;
+
+        // Half of the face glows, the other half shares its colours
+        let mut mask = [[false; 64]; 64];
+        for row in &mut mask[8..16] {
+            row[8..12].fill(true);
+        }
+        let mut skin = image.clone();
+        let palette = generate_emissive_palette(&mut skin, &mask)?;
+        let nudged = skin.clone();
+        write_emissive_palette(&mut skin, &palette)?;
+        assert!(!palette.0.is_empty());
+        assert_eq!(extract_emissive_palette(&skin)?, Some(palette.clone()));
+
+        let emissive = apply_emissive_palette(
+            &mut skin.clone(),
+            &extract_emissive_palette(&skin)?.unwrap(),
+        )?;
+        for (x, y, pixel) in emissive.enumerate_pixels() {
+            // The data blocks are not drawn, so they may match the palette
+            if (32..36).contains(&y) && ((0..4).contains(&x) || (52..56).contains(&x)) {
+                continue;
+            }
+            let glows = mask[y as usize][x as usize] && image.get_pixel(x, y)[3] > 0;
+            assert_eq!(pixel[3] > 0, glows, "({x}, {y})");
+        }
+
+        // Nudged pixels only moved by one unit
+        assert_ne!(nudged, image);
+        for (before, after) in image.pixels().zip(nudged.pixels()) {
+            let difference: Vec<u8> = (0..4).map(|i| before[i].abs_diff(after[i])).collect();
+            assert!(difference.iter().all(|&d| d <= 1), "{before:?} {after:?}");
+        }
+
+        // Every texel of an HD skin takes the value of the mask entry it belongs to
+        let mut hd = rescale_skin(&image, 2)?;
+        assert_eq!(generate_emissive_palette(&mut hd, &mask)?, palette);
+        assert_eq!(hd, rescale_skin(&nudged, 2)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn nudging_finds_a_free_colour_in_a_corner() {
+        let black = Rgb([0, 0, 0]);
+        // Black and every colour one unit away from it
+        let mut palette = vec![black];
+        for offset in 1..8u8 {
+            palette.push(Rgb([offset >> 2, (offset >> 1) & 1, offset & 1]));
+        }
+        palette.extend((1..=8).map(|r| Rgb([r + 2, 0, 0])));
+        assert_eq!(palette.len(), MAX_EMISSIVE_COLORS);
+
+        assert_eq!(nudge(Rgb([0, 0, 1]), &[Rgb([0, 0, 1])]), Rgb([0, 0, 0]));
+        assert_eq!(nudge(black, &palette), Rgb([0, 0, 2]));
+    }
+
+    #[test]
+    fn generating_a_palette_checks_its_input() {
+        let mut skin = RgbaImage::from_fn(64, 64, |x, _| image::Rgba([x as u8, 0, 0, 255]));
+        assert!(matches!(
+            generate_emissive_palette(&mut RgbaImage::new(64, 32), &[[true; 64]; 64]),
+            Err(EarsError::InvalidSkinSize(64, 32))
+        ));
+        assert!(matches!(
+            generate_emissive_palette(&mut skin, &[[true; 64]; 64]),
+            Err(EarsError::TooManyEmissiveColors(64))
+        ));
+    }
 }
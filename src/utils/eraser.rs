@@ -1,6 +1,7 @@
 use image::RgbaImage;
 
-use crate::alfalfa::utils::{EraseRegion, EraseRegionsProvider};
+use crate::alfalfa::utils::EraseRegionsProvider;
+use crate::layout::skin_scale;
 use crate::utils::errors::Result;
 
 pub fn process_erase_regions(image: &mut RgbaImage) -> Result<()> {
@@ -15,11 +16,18 @@ pub(crate) fn apply_erase_regions(
     image: &mut RgbaImage,
     alfalfa: &crate::alfalfa::AlfalfaData,
 ) -> Result<()> {
+    // Erase regions are stored in 64x64 skin coordinates, so HD skins erase the matching scaled area
+    let scale = skin_scale(image).unwrap_or(1);
     if let Some(regions) = alfalfa.get_erase_regions()? {
         for region in regions {
-            for x in region.x..region.x + region.width {
-                for y in region.y..region.y + region.height {
-                    if let Some(pixel) = image.get_pixel_mut_checked(x as u32, y as u32) {
+            let (x1, y1) = (region.x as u32 * scale, region.y as u32 * scale);
+            let (x2, y2) = (
+                x1 + region.width as u32 * scale,
+                y1 + region.height as u32 * scale,
+            );
+            for x in x1..x2 {
+                for y in y1..y2 {
+                    if let Some(pixel) = image.get_pixel_mut_checked(x, y) {
                         *pixel = image::Rgba([0, 0, 0, 0]);
                     }
                 }
@@ -31,6 +39,8 @@ pub(crate) fn apply_erase_regions(
 
 #[cfg(test)]
 mod tests {
+    use image::imageops::{FilterType, resize};
+
     use super::*;
     use crate::utils::errors::Result;
 
@@ -55,4 +65,18 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn eraser_works_on_hd_skins() -> Result<()> {
+        let image = image::open("test_images/ears_v1_nickac_sample.png").unwrap();
+        let mut image = resize(&image.to_rgba8(), 128, 128, FilterType::Nearest);
+
+        process_erase_regions(&mut image)?;
+        let expected_image = image::open("test_images/ears_v1_nickac_sample_erased.png").unwrap();
+        let expected_image = resize(&expected_image.to_rgba8(), 128, 128, FilterType::Nearest);
+
+        assert_eq!(image, expected_image);
+
+        Ok(())
+    }
 }
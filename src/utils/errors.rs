@@ -1,6 +1,9 @@
 use image::ImageError;
 use thiserror::Error;
 
+use crate::features::textures::FeatureTexture;
+use crate::utils::model::AlfalfaDamage;
+
 #[derive(Debug, Error)]
 pub enum EarsError {
     #[error("Image error: {0}")]
@@ -9,6 +12,10 @@ pub enum EarsError {
     InvalidMagicPixelLocation(u32),
     #[error("Invalid Alfalfa pixel position: ({0}, {1})")]
     InvalidAlfalfaPixelPosition(u32, u32),
+    #[error(
+        "Cannot store Alfalfa data in a {0}x{1} image - it must be square and a multiple of 64"
+    )]
+    InvalidAlfalfaImageSize(u32, u32),
     #[error("Invalid Alfalfa pixel position: ({0}, {1})")]
     InvalidPixelLocation(u32, u32),
     #[error("IO error ({0}): {1}")]
@@ -28,6 +35,28 @@ pub enum EarsError {
     /* Cannot write more than 1428 bytes of data (got "+bys.length+" bytes) */
     #[error("Cannot write more than 1428 bytes of data (got {0} bytes)")]
     AlfalfaDataTooLarge(usize),
+    #[error("Invalid erase region position: ({0}, {1}) - x and y must be at most 63")]
+    InvalidEraseRegionPosition(u8, u8),
+    #[error("Invalid erase region size: {0}x{1} - width and height must be between 1 and 32")]
+    InvalidEraseRegionSize(u8, u8),
+    #[error("Cannot write the {0:?} texture - its feature is not enabled")]
+    FeatureTextureNotEnabled(FeatureTexture),
+    #[error("Invalid size for the {0:?} texture: got {1}x{2}, expected {3}x{4}")]
+    InvalidFeatureTextureSize(FeatureTexture, u32, u32, u32, u32),
+    #[error("Damaged Alfalfa data: {0}")]
+    DamagedAlfalfaData(AlfalfaDamage),
+    #[error("Invalid cape size: {0}x{1} - it must be 64x32, or a multiple of it for HD capes")]
+    InvalidCapeSize(u32, u32),
+    #[error("Cannot convert a {0}x{1} HD cape without changing its resolution")]
+    UnsupportedHdCape(u32, u32),
+    #[error("Invalid skin size: {0}x{1} - it must be square and a multiple of 64")]
+    InvalidSkinSize(u32, u32),
+    #[error("Invalid skin scale: {0} - it must be at least 1")]
+    InvalidSkinScale(u32),
+    #[error("Cannot make {0} colours glow - the emissive palette holds at most 16")]
+    TooManyEmissiveColors(usize),
+    #[error("Invalid render size: {0}x{1} with {2}x supersampling is too large")]
+    InvalidRenderSize(u32, u32, u32),
     #[error("Unable to convert big uint to u32")]
     UnableToConvertBigUintToU32,
 }
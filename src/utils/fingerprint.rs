@@ -0,0 +1,282 @@
+use image::{Rgb, RgbaImage};
+
+use crate::alfalfa::{AlfalfaDataKey, read_alfalfa_partial};
+use crate::features::EarsFeatures;
+use crate::features::textures::processed_feature_regions;
+use crate::layout::{BodyPart, DATA_BLOCK, Layer, TextureRegion, layer_regions, skin_scale};
+use crate::parser::{EarsFeaturesWriter, v1::writer::EarsWriterV1};
+use crate::utils::errors::Result;
+use crate::utils::processed::entry_enabled;
+use crate::utils::{ProcessedSkin, SkinModel, extract_emissive_palette, upgrade_skin_if_needed};
+
+/// Returns a key that two skins share when Ears draws them the same, for caching renders.
+///
+/// The key is built from a canonical form of the skin rather than its raw pixels:
+///
+/// - The features, as the bits Ears stores them with, so unused magic pixels and values that
+///   quantise the same don't matter.
+/// - The arm model found by [`SkinModel::detect`].
+/// - The texels Ears draws, after erase regions and alpha stripping, with the colour of fully
+///   transparent texels ignored.
+/// - The wing and cape textures when they are enabled, or their PNG bytes when they can't be
+///   decoded, other Alfalfa entries sorted by key, and the emissive palette sorted.
+///
+/// Damaged Alfalfa data doesn't fail the fingerprint, the entries read before the damage are
+/// used. The key is stable across runs and platforms, so it can be stored.
+pub fn fingerprint(image: &RgbaImage) -> Result<u64> {
+    let base = upgrade_skin_if_needed(image.clone());
+    let alfalfa = read_alfalfa_partial(&base)?.map(|partial| partial.data);
+    let palette = extract_emissive_palette(&base)?;
+    let processed = ProcessedSkin::process(base, alfalfa, SkinModel::detect(image))?;
+    let mut hasher = Fnv::new();
+
+    hash_features(&mut hasher, processed.features.as_ref())?;
+    hasher.write(&[processed.model as u8]);
+
+    let drawn = drawn_regions(processed.features.as_ref(), processed.model);
+    let scale = skin_scale(&processed.base).unwrap_or(1);
+    for texture in [
+        Some(&processed.base),
+        processed.displaced.as_ref(),
+        processed.emissive.as_ref(),
+        processed.displaced_emissive.as_ref(),
+    ] {
+        hasher.write_optional(texture, |hasher, texture| {
+            for (x, y) in drawn
+                .iter()
+                .flat_map(|region| region.scaled(scale).pixels())
+            {
+                hash_texel(hasher, texture.get_pixel(x, y).0);
+            }
+        });
+    }
+
+    for (key, texture) in [
+        (AlfalfaDataKey::Wings, processed.wing.as_ref()),
+        (AlfalfaDataKey::Cape, processed.cape.as_ref()),
+    ] {
+        let png = processed
+            .alfalfa
+            .as_ref()
+            .and_then(|alfalfa| alfalfa.get_data(key))
+            .filter(|_| entry_enabled(processed.features.as_ref(), key));
+        match (texture, png) {
+            (Some(texture), _) => {
+                hasher.write(&[1]);
+                hasher.write(&texture.width().to_le_bytes());
+                hasher.write(&texture.height().to_le_bytes());
+                for pixel in texture.pixels() {
+                    hash_texel(&mut hasher, pixel.0);
+                }
+            }
+            (None, Some(png)) => {
+                hasher.write(&[2]);
+                hasher.write_bytes(png);
+            }
+            (None, None) => hasher.write(&[0]),
+        }
+    }
+
+    // Erase regions are already applied, and the wing and cape are hashed as textures
+    let skipped: [&str; 3] = [
+        AlfalfaDataKey::Erase.into(),
+        AlfalfaDataKey::Wings.into(),
+        AlfalfaDataKey::Cape.into(),
+    ];
+    let mut entries: Vec<(&String, &Vec<u8>)> = processed
+        .alfalfa
+        .iter()
+        .flat_map(|alfalfa| &alfalfa.data)
+        .filter(|(key, _)| !skipped.contains(&key.as_str()))
+        .collect();
+    entries.sort();
+    hasher.write(&(entries.len() as u64).to_le_bytes());
+    for (key, value) in entries {
+        hasher.write_bytes(key.as_bytes());
+        hasher.write_bytes(value);
+    }
+
+    let mut palette: Vec<Rgb<u8>> = palette.map(|palette| palette.0).unwrap_or_default();
+    palette.sort_by_key(|colour| colour.0);
+    palette.dedup();
+    hasher.write(&(palette.len() as u64).to_le_bytes());
+    for colour in palette {
+        hasher.write(&colour.0);
+    }
+
+    Ok(hasher.finish())
+}
+
+fn hash_features(hasher: &mut Fnv, features: Option<&EarsFeatures>) -> Result<()> {
+    let Some(features) = features else {
+        hasher.write(&[0]);
+        return Ok(());
+    };
+
+    let mut block = RgbaImage::new(64, 64);
+    EarsWriterV1::write(&mut block, features)?;
+    hasher.write(&[1]);
+    for (x, y) in DATA_BLOCK.pixels() {
+        hasher.write(&block.get_pixel(x, y).0);
+    }
+    Ok(())
+}
+
+/// Returns the parts of a processed skin that Ears draws.
+fn drawn_regions(features: Option<&EarsFeatures>, model: SkinModel) -> Vec<TextureRegion> {
+    let mut regions: Vec<TextureRegion> = BodyPart::ALL
+        .into_iter()
+        .flat_map(|part| Layer::ALL.map(|layer| (part, layer)))
+        .flat_map(|(part, layer)| layer_regions(part, layer, model))
+        .collect();
+
+    if let Some(features) = features {
+        regions.extend(
+            processed_feature_regions(features)
+                .iter()
+                .map(|region| region.region),
+        );
+    }
+    regions
+}
+
+fn hash_texel(hasher: &mut Fnv, [r, g, b, a]: [u8; 4]) {
+    if a == 0 {
+        hasher.write(&[0; 4]);
+    } else {
+        hasher.write(&[r, g, b, a]);
+    }
+}
+
+/// 64-bit FNV-1a, which unlike [`std::hash::DefaultHasher`] gives the same hash everywhere.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Writes `bytes` after their length, so that consecutive values can't run into each other.
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    fn write_optional<T>(&mut self, value: Option<T>, write: impl FnOnce(&mut Self, T)) {
+        match value {
+            Some(value) => {
+                self.write(&[1]);
+                write(self, value);
+            }
+            None => self.write(&[0]),
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+    use crate::alfalfa::test_support::write_damaged_alfalfa;
+    use crate::alfalfa::{AlfalfaData, read_alfalfa, write_alfalfa};
+    use crate::features::data::wing::WingData;
+    use crate::parser::EarsParser;
+
+    #[test]
+    fn invisible_changes_keep_the_fingerprint() -> Result<()> {
+        let image = image::open("test_images/emissive-before.png")
+            .unwrap()
+            .to_rgba8();
+        let key = fingerprint(&image)?;
+        assert_eq!(fingerprint(&image)?, key);
+
+        // Nothing is drawn from the corner of the head texture
+        let mut changed = image.clone();
+        changed.put_pixel(0, 0, Rgba([1, 2, 3, 255]));
+        assert_eq!(fingerprint(&changed)?, key);
+
+        // Alpha is stripped from the face
+        let mut changed = image.clone();
+        changed.get_pixel_mut(9, 9).0[3] = 128;
+        assert_eq!(fingerprint(&changed)?, key);
+
+        // The last data pixel is past the bits the features are stored in
+        let mut changed = image.clone();
+        changed.put_pixel(3, 35, Rgba([1, 2, 3, 255]));
+        assert_eq!(EarsParser::parse(&changed)?, EarsParser::parse(&image)?);
+        assert_eq!(fingerprint(&changed)?, key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn visible_changes_change_the_fingerprint() -> Result<()> {
+        let image = image::open("test_images/emissive-before.png")
+            .unwrap()
+            .to_rgba8();
+        let key = fingerprint(&image)?;
+
+        let mut changed = image.clone();
+        let pixel = changed.get_pixel_mut(9, 9);
+        pixel.0[0] = pixel.0[0].wrapping_add(1);
+        assert_ne!(fingerprint(&changed)?, key);
+
+        let mut features = EarsParser::parse(&image)?.unwrap();
+        features.cape_enabled = !features.cape_enabled;
+        let mut changed = image.clone();
+        EarsWriterV1::write(&mut changed, &features)?;
+        assert_ne!(fingerprint(&changed)?, key);
+
+        assert_ne!(fingerprint(&RgbaImage::new(64, 64))?, key);
+
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_skins_share_the_fingerprint_of_their_upgrade() -> Result<()> {
+        let legacy = image::open("test_images/notch_original.png")
+            .unwrap()
+            .to_rgba8();
+        let upgraded = upgrade_skin_if_needed(legacy.clone());
+
+        assert_eq!(fingerprint(&legacy)?, fingerprint(&upgraded)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn damaged_alfalfa_and_undecodable_wings_are_fingerprinted() -> Result<()> {
+        let mut damaged = RgbaImage::new(64, 64);
+        write_damaged_alfalfa(&mut damaged)?;
+        assert!(read_alfalfa(&damaged).is_err());
+        fingerprint(&damaged)?;
+
+        let wings = |png: Vec<u8>| -> Result<u64> {
+            let mut image = RgbaImage::new(64, 64);
+            let features = EarsFeatures {
+                wing: Some(WingData::default()),
+                ..Default::default()
+            };
+            EarsWriterV1::write(&mut image, &features)?;
+            let mut alfalfa = AlfalfaData::new();
+            alfalfa.set_data(AlfalfaDataKey::Wings, png);
+            write_alfalfa(&alfalfa, &mut image)?;
+            fingerprint(&image)
+        };
+        assert_eq!(wings(vec![1, 2, 3])?, wings(vec![1, 2, 3])?);
+        assert_ne!(wings(vec![1, 2, 3])?, wings(vec![1, 2, 4])?);
+
+        Ok(())
+    }
+}
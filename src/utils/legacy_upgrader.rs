@@ -1,6 +1,32 @@
 use image::{RgbaImage, imageops};
 use itertools::Either;
 
+use crate::alfalfa::read_alfalfa_partial;
+use crate::layout::{BodyPart, Face, Layer, face_region, layer_regions, skin_scale};
+use crate::parser::EarsParser;
+use crate::utils::SkinModel;
+
+/// What was lost when downgrading a skin to the legacy 64x32 format.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DowngradeReport {
+    /// The parts whose overlay layer had visible pixels. Legacy skins only have the hat.
+    pub overlays: Vec<BodyPart>,
+    /// The left limbs that weren't a mirror of the right ones, which legacy skins use for both.
+    pub left_limbs: Vec<BodyPart>,
+    /// Whether the skin had Ears features, which are stored below the legacy skin.
+    pub ears_features: bool,
+    /// The keys of the skin's Alfalfa entries, sorted. The payload is partly stored below the
+    /// legacy skin, so every entry is lost, including one cut short by damage.
+    pub alfalfa: Vec<String>,
+}
+
+impl DowngradeReport {
+    /// Whether the downgraded skin looks the same as the original one.
+    pub fn is_lossless(&self) -> bool {
+        self == &Self::default()
+    }
+}
+
 #[inline]
 fn check_has_transparency(image: &RgbaImage, x1: u32, y1: u32, x2: u32, y2: u32) -> bool {
     // Assume that our values are from 0 to 64 and scale them to the image's dimensions
@@ -15,10 +41,10 @@ fn check_has_transparency(image: &RgbaImage, x1: u32, y1: u32, x2: u32, y2: u32)
 
     for y in min_dy..max_dy {
         for x in min_dx..max_dx {
-            if let Some(pixel) = image.get_pixel_checked(x, y) {
-                if pixel.0[3] < 128 {
-                    return true;
-                }
+            if let Some(pixel) = image.get_pixel_checked(x, y)
+                && pixel.0[3] < 128
+            {
+                return true;
             }
         }
     }
@@ -112,18 +138,23 @@ pub fn upgrade_skin_if_needed(image: RgbaImage) -> RgbaImage {
         let mut new_image = RgbaImage::new(image.width(), image.height() + (32f32 * scale) as u32);
         imageops::replace(&mut new_image, &image, 0, 0);
 
-        copy_rect(&mut new_image, (24, 48), (20, 52), (4, 16), (8, 20));
-        copy_rect(&mut new_image, (28, 48), (24, 52), (8, 16), (12, 20));
-        copy_rect(&mut new_image, (20, 52), (16, 64), (8, 20), (12, 32));
-        copy_rect(&mut new_image, (24, 52), (20, 64), (4, 20), (8, 32));
-        copy_rect(&mut new_image, (28, 52), (24, 64), (0, 20), (4, 32));
-        copy_rect(&mut new_image, (32, 52), (28, 64), (12, 20), (16, 32));
-        copy_rect(&mut new_image, (40, 48), (36, 52), (44, 16), (48, 20));
-        copy_rect(&mut new_image, (44, 48), (40, 52), (48, 16), (52, 20));
-        copy_rect(&mut new_image, (36, 52), (32, 64), (48, 20), (52, 32));
-        copy_rect(&mut new_image, (40, 52), (36, 64), (44, 20), (48, 32));
-        copy_rect(&mut new_image, (44, 52), (40, 64), (40, 20), (44, 32));
-        copy_rect(&mut new_image, (48, 52), (44, 64), (52, 20), (56, 32));
+        // The left limbs are the right ones mirrored
+        for (left, right) in [
+            (BodyPart::LeftLeg, BodyPart::RightLeg),
+            (BodyPart::LeftArm, BodyPart::RightArm),
+        ] {
+            for face in Face::ALL {
+                let to = face_region(left, Layer::Base, face, SkinModel::Classic);
+                let from = face_region(right, Layer::Base, face.mirrored(), SkinModel::Classic);
+                copy_rect(
+                    &mut new_image,
+                    (to.x + to.width, to.y),
+                    (to.x, to.y + to.height),
+                    (from.x, from.y),
+                    (from.x + from.width, from.y + from.height),
+                );
+            }
+        }
 
         set_area_transparent(&mut new_image, 32, 0, 64, 32);
         set_area_transparent(&mut new_image, 0, 32, 16, 48);
@@ -139,6 +170,56 @@ pub fn upgrade_skin_if_needed(image: RgbaImage) -> RgbaImage {
     }
 }
 
+/// Downgrades a 64x64 skin, or an HD multiple of it, to the legacy 2:1 format, reporting what
+/// was lost on the way. Other images are returned as they are, with nothing reported.
+pub fn downgrade_skin(image: &RgbaImage) -> (RgbaImage, DowngradeReport) {
+    let Some(scale) = skin_scale(image) else {
+        return (image.clone(), DowngradeReport::default());
+    };
+
+    let legacy = imageops::crop_imm(image, 0, 0, image.width(), image.height() / 2).to_image();
+    let upgraded = upgrade_skin_if_needed(legacy.clone());
+
+    let mut report = DowngradeReport {
+        ears_features: matches!(EarsParser::parse(image), Ok(Some(_))),
+        alfalfa: alfalfa_keys(image),
+        ..Default::default()
+    };
+    let pixels = |part, layer| {
+        layer_regions(part, layer, SkinModel::Classic)
+            .into_iter()
+            .flat_map(move |region| region.scaled(scale).pixels())
+    };
+    for part in BodyPart::ALL {
+        if part != BodyPart::Head
+            && pixels(part, Layer::Overlay).any(|(x, y)| image.get_pixel(x, y)[3] != 0)
+        {
+            report.overlays.push(part);
+        }
+
+        // Base layers are opaque, so only their colour matters
+        if matches!(part, BodyPart::LeftArm | BodyPart::LeftLeg)
+            && pixels(part, Layer::Base)
+                .any(|(x, y)| image.get_pixel(x, y).0[..3] != upgraded.get_pixel(x, y).0[..3])
+        {
+            report.left_limbs.push(part);
+        }
+    }
+
+    (legacy, report)
+}
+
+fn alfalfa_keys(image: &RgbaImage) -> Vec<String> {
+    let Ok(Some(partial)) = read_alfalfa_partial(image) else {
+        return Vec::new();
+    };
+    let mut keys: Vec<String> = partial.data.into_data().into_keys().collect();
+    keys.extend(partial.damage.and_then(|damage| damage.entry));
+    keys.sort();
+    keys.dedup();
+    keys
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -181,4 +262,56 @@ mod tests {
             "test_images/mister_fix_upgraded_x256.png",
         );
     }
+
+    #[test]
+    fn downgrading_vanilla_skins_is_lossless() {
+        let image = image::open("test_images/notch_upgraded.png")
+            .unwrap()
+            .to_rgba8();
+
+        let (legacy, report) = downgrade_skin(&image);
+
+        assert_eq!(legacy.dimensions(), (64, 32));
+        assert!(report.is_lossless(), "{report:?}");
+    }
+
+    #[test]
+    fn downgrading_reports_what_was_lost() {
+        let mut image = image::open("test_images/ears_v1_nickac_sample.png")
+            .unwrap()
+            .to_rgba8();
+        image.put_pixel(20, 52, image::Rgba([1, 2, 3, 255]));
+
+        let (legacy, report) = downgrade_skin(&image);
+
+        assert_eq!(legacy.dimensions(), (64, 32));
+        assert!(report.ears_features);
+        assert_eq!(report.alfalfa, ["cape", "erase"]);
+        assert!(report.left_limbs.contains(&BodyPart::LeftLeg));
+        assert!(report.overlays.contains(&BodyPart::Torso));
+        assert!(!report.overlays.contains(&BodyPart::Head));
+
+        let (_, report) = downgrade_skin(&image::imageops::resize(
+            &image,
+            128,
+            128,
+            image::imageops::FilterType::Nearest,
+        ));
+        assert_eq!(report.alfalfa, ["cape", "erase"]);
+        assert!(report.left_limbs.contains(&BodyPart::LeftLeg));
+    }
+
+    #[test]
+    fn downgrading_reports_damaged_alfalfa_entries() -> crate::utils::errors::Result<()> {
+        let mut image = RgbaImage::new(64, 64);
+        crate::alfalfa::test_support::write_damaged_alfalfa(&mut image)?;
+
+        let (_, report) = downgrade_skin(&image);
+
+        assert!(!report.ears_features);
+        // The cape entry is cut short, but it is still lost
+        assert_eq!(report.alfalfa, ["cape", "erase"]);
+
+        Ok(())
+    }
 }
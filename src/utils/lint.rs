@@ -0,0 +1,262 @@
+use image::{Rgb, RgbaImage};
+
+use crate::alfalfa::utils::{EraseRegion, EraseRegionsProvider};
+use crate::alfalfa::{AlfalfaDamage, read_alfalfa_partial};
+use crate::features::EarsFeatures;
+use crate::features::data::leg::LegMode;
+use crate::layout::{DATA_BLOCK, EMISSIVE_PALETTE, JACKET_BACK, TAIL, TextureRegion, skin_scale};
+use crate::parser::write_features;
+use crate::utils::alpha::{displaced_leg_regions, forced_opaque_regions};
+use crate::utils::errors::Result;
+use crate::utils::{SkinModel, extract_emissive_palette, upgrade_skin_if_needed};
+
+/// How many texels of a 64x64 skin, as many as a face of the head, an emissive palette colour
+/// has to cover before it is reported.
+const LARGE_EMISSIVE_AREA: usize = 64;
+
+/// Paint that Ears hides, moves or ignores. Pixels are given in the coordinates of the skin, after
+/// upgrading legacy 64x32 skins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkinWarning {
+    /// Paint in the parts of the legs that digitigrade legs draw on their bent lower leg instead.
+    DisplacedLegPaint { pixels: Vec<(u32, u32)> },
+    /// Art where the features are stored, which writing them replaces.
+    ArtUnderDataBlock { pixels: Vec<(u32, u32)> },
+    /// Semi-transparent pixels in parts of the skin that are drawn fully opaque.
+    ForcedOpaque { pixels: Vec<(u32, u32)> },
+    /// A large area in a colour of the emissive palette, which glows along with it.
+    EmissiveColourArea {
+        colour: Rgb<u8>,
+        pixels: Vec<(u32, u32)>,
+    },
+    /// An erase region over pixels that are already transparent.
+    EmptyEraseRegion { region: EraseRegion },
+    /// Paint in the tail and jacket back textures, which the tail swaps with each other.
+    SwappedTail { pixels: Vec<(u32, u32)> },
+    /// Alfalfa data that stops being readable part way, losing the entries after the damage.
+    DamagedAlfalfa { damage: AlfalfaDamage },
+}
+
+/// Looks for paint in `image` that Ears won't draw where it is painted when the skin has
+/// `features`.
+pub fn lint_skin(image: &RgbaImage, features: &EarsFeatures) -> Result<Vec<SkinWarning>> {
+    let image = upgrade_skin_if_needed(image.clone());
+    let scale = skin_scale(&image).unwrap_or(1);
+    let (alfalfa, damage) = match read_alfalfa_partial(&image)? {
+        Some(partial) => (Some(partial.data), partial.damage),
+        None => (None, None),
+    };
+    let visible = |region: TextureRegion| -> Vec<(u32, u32)> {
+        region
+            .scaled(scale)
+            .pixels()
+            .filter(|&(x, y)| image.get_pixel_checked(x, y).is_some_and(|p| p[3] > 0))
+            .collect()
+    };
+
+    let mut warnings = Vec::new();
+
+    if let Some(damage) = damage {
+        warnings.push(SkinWarning::DamagedAlfalfa { damage });
+    }
+
+    if features.leg_mode != LegMode::Plantigrade {
+        let pixels: Vec<_> = displaced_leg_regions(features.leg_mode)
+            .into_iter()
+            .flat_map(|(region, _)| visible(region))
+            .collect();
+        if !pixels.is_empty() {
+            warnings.push(SkinWarning::DisplacedLegPaint { pixels });
+        }
+    }
+
+    let mut written = image.clone();
+    write_features(&mut written, features)?;
+    let pixels: Vec<_> = visible(DATA_BLOCK)
+        .into_iter()
+        .filter(|&(x, y)| image.get_pixel(x, y) != written.get_pixel(x, y))
+        .collect();
+    if !pixels.is_empty() {
+        warnings.push(SkinWarning::ArtUnderDataBlock { pixels });
+    }
+
+    // Alfalfa data is stored in the alpha of the same parts, always with the top bit set
+    let model = SkinModel::detect(&image);
+    let pixels: Vec<_> = forced_opaque_regions(Some(features.leg_mode), model)
+        .into_iter()
+        .flat_map(|region| region.scaled(scale).pixels())
+        .filter(|&(x, y)| {
+            let alpha = image.get_pixel(x, y)[3];
+            alpha > 0 && alpha < u8::MAX && (alfalfa.is_none() || alpha & 0x80 == 0)
+        })
+        .collect();
+    if !pixels.is_empty() {
+        warnings.push(SkinWarning::ForcedOpaque { pixels });
+    }
+
+    if features.emissive
+        && let Some(palette) = extract_emissive_palette(&image)?
+    {
+        let palette_block = EMISSIVE_PALETTE.scaled(scale);
+        for colour in palette.0 {
+            let pixels: Vec<_> = image
+                .enumerate_pixels()
+                .filter(|(x, y, pixel)| {
+                    pixel[3] > 0 && pixel.0[..3] == colour.0 && !palette_block.contains(*x, *y)
+                })
+                .map(|(x, y, _)| (x, y))
+                .collect();
+            if pixels.len() >= LARGE_EMISSIVE_AREA * (scale * scale) as usize {
+                warnings.push(SkinWarning::EmissiveColourArea { colour, pixels });
+            }
+        }
+    }
+
+    if let Some(regions) = alfalfa
+        .as_ref()
+        .map(|alfalfa| alfalfa.get_erase_regions())
+        .transpose()?
+        .flatten()
+    {
+        for region in regions {
+            let area = TextureRegion::new(
+                region.x.into(),
+                region.y.into(),
+                region.width.into(),
+                region.height.into(),
+            );
+            if visible(area).is_empty() {
+                warnings.push(SkinWarning::EmptyEraseRegion { region });
+            }
+        }
+    }
+
+    if features.tail.is_some_and(|tail| tail.swap_jacket_back) {
+        let pixels: Vec<_> = [TAIL, JACKET_BACK].into_iter().flat_map(visible).collect();
+        if !pixels.is_empty() {
+            warnings.push(SkinWarning::SwappedTail { pixels });
+        }
+    }
+
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+    use crate::alfalfa::test_support::write_damaged_alfalfa;
+    use crate::alfalfa::{AlfalfaData, write_alfalfa};
+    use crate::features::data::tail::{TailData, TailMode};
+    use crate::parser::EarsParser;
+    use crate::utils::{EarsEmissivePalette, write_emissive_palette};
+
+    fn opaque(image: &RgbaImage, region: TextureRegion) -> RgbaImage {
+        let mut image = image.clone();
+        for (x, y) in region.pixels() {
+            image.put_pixel(x, y, Rgba([10, 20, 30, 255]));
+        }
+        image
+    }
+
+    #[test]
+    fn clean_skins_have_no_warnings() -> Result<()> {
+        let image = image::open("test_images/ears_v1_nickac_sample.png")
+            .unwrap()
+            .to_rgba8();
+        let features = EarsParser::parse(&image)?.unwrap();
+
+        assert_eq!(lint_skin(&image, &features)?, []);
+
+        Ok(())
+    }
+
+    #[test]
+    fn finds_hidden_paint() -> Result<()> {
+        let skin = opaque(&RgbaImage::new(64, 64), TextureRegion::new(0, 16, 56, 16));
+        let features = EarsFeatures {
+            leg_mode: LegMode::DigitigradeFull,
+            ..Default::default()
+        };
+
+        let warnings = lint_skin(&skin, &features)?;
+        let [SkinWarning::DisplacedLegPaint { pixels }] = warnings.as_slice() else {
+            panic!("{warnings:?}");
+        };
+        assert!(pixels.contains(&(4, 20)));
+        assert!(!pixels.contains(&(20, 20)));
+
+        // Art under the data block, and a see-through face
+        let mut skin = opaque(&skin, DATA_BLOCK);
+        skin.put_pixel(9, 9, Rgba([10, 20, 30, 100]));
+        let warnings = lint_skin(&skin, &EarsFeatures::default())?;
+        assert_eq!(
+            warnings,
+            [
+                SkinWarning::ArtUnderDataBlock {
+                    pixels: DATA_BLOCK.pixels().collect()
+                },
+                SkinWarning::ForcedOpaque {
+                    pixels: vec![(9, 9)]
+                },
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn finds_emissive_areas_empty_erase_regions_and_swapped_tails() -> Result<()> {
+        let features = EarsFeatures {
+            emissive: true,
+            tail: Some(TailData {
+                mode: TailMode::Down,
+                swap_jacket_back: true,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let mut skin = opaque(&RgbaImage::new(64, 64), TextureRegion::new(0, 16, 64, 16));
+        write_features(&mut skin, &features)?;
+        write_emissive_palette(&mut skin, &EarsEmissivePalette(vec![Rgb([10, 20, 30])]))?;
+
+        let mut alfalfa = AlfalfaData::new();
+        let empty = EraseRegion::new(0, 0, 8, 8)?;
+        alfalfa.set_erase_regions(&[empty, EraseRegion::new(0, 20, 4, 4)?])?;
+        write_alfalfa(&alfalfa, &mut skin)?;
+
+        let warnings = lint_skin(&skin, &features)?;
+        assert!(
+            matches!(
+                &warnings[..],
+                [
+                    SkinWarning::EmissiveColourArea { colour: Rgb([10, 20, 30]), pixels },
+                    SkinWarning::EmptyEraseRegion { region },
+                    SkinWarning::SwappedTail { .. },
+                ] if pixels.len() == 64 * 16 && *region == empty
+            ),
+            "{warnings:?}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn reports_damaged_alfalfa_data() -> Result<()> {
+        let mut skin = RgbaImage::new(64, 64);
+        write_damaged_alfalfa(&mut skin)?;
+
+        let warnings = lint_skin(&skin, &EarsFeatures::default())?;
+        assert!(
+            matches!(
+                &warnings[..],
+                [SkinWarning::DamagedAlfalfa { damage }, ..]
+                    if damage.entry.as_deref() == Some("cape")
+            ),
+            "{warnings:?}"
+        );
+
+        Ok(())
+    }
+}
@@ -7,13 +7,27 @@ mod cape;
 mod emissive;
 mod eraser;
 pub mod errors;
+mod fingerprint;
 mod legacy_upgrader;
+mod lint;
+mod processed;
+mod rescale;
 mod skin;
+mod skin_model;
 
-pub use alpha::{strip_alpha, strip_alpha_for_features};
-pub use cape::convert_ears_cape_to_mojang_cape;
+pub(crate) use alpha::displaced_leg_regions;
+pub use alpha::{strip_alpha, strip_alpha_for_features, strip_alpha_for_model};
+pub use cape::{
+    HdCapeHandling, convert_ears_cape_to_mojang_cape, convert_mojang_cape_to_ears_cape,
+    generate_elytra,
+};
 pub use eraser::process_erase_regions;
-pub use legacy_upgrader::upgrade_skin_if_needed;
+pub use fingerprint::fingerprint;
+pub use legacy_upgrader::{DowngradeReport, downgrade_skin, upgrade_skin_if_needed};
+pub use lint::{SkinWarning, lint_skin};
+pub use processed::ProcessedSkin;
+pub use rescale::rescale_skin;
 pub use skin::{apply_erase_displaced_regions, extract_displaced_skin, swap_jacket_back_and_tail};
+pub use skin_model::SkinModel;
 
 pub use emissive::*;
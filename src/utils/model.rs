@@ -15,6 +15,48 @@ pub struct AlfalfaData {
     pub(crate) data: HashMap<String, Vec<u8>>,
 }
 
+/// Describes where decoding of a damaged Alfalfa payload stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AlfalfaDamage {
+    /// Offset of the byte that could not be read, counted from the start of the payload.
+    pub offset: usize,
+    /// Skin pixel holding that byte, or `None` if the payload ended before it.
+    pub pixel: Option<(u32, u32)>,
+    /// Key of the entry that was being decoded, if the damage is inside an entry.
+    pub entry: Option<String>,
+    pub reason: &'static str,
+}
+
+impl AlfalfaDamage {
+    pub(crate) fn in_entry(self, key: &str) -> Self {
+        Self {
+            entry: Some(key.to_owned()),
+            ..self
+        }
+    }
+}
+
+impl std::fmt::Display for AlfalfaDamage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.reason, self.offset)?;
+        if let Some(entry) = &self.entry {
+            write!(f, " of entry {entry}")?;
+        }
+        match self.pixel {
+            Some((x, y)) => write!(f, " (pixel {x}, {y})"),
+            None => write!(f, " (past the end of the data)"),
+        }
+    }
+}
+
+/// The Alfalfa entries that could be read from a skin, and where decoding stopped if the
+/// payload was damaged.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PartialAlfalfaData {
+    pub data: AlfalfaData,
+    pub damage: Option<AlfalfaDamage>,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AlfalfaDataKey {
     Erase,
@@ -0,0 +1,308 @@
+use image::RgbaImage;
+
+use crate::alfalfa::{AlfalfaData, AlfalfaDataKey, read_alfalfa};
+use crate::features::EarsFeatures;
+use crate::features::data::wing::WingMode;
+use crate::parser::EarsParser;
+use crate::utils::eraser::apply_erase_regions;
+use crate::utils::errors::Result;
+use crate::utils::{
+    SkinModel, apply_emissive_palette, apply_erase_displaced_regions, extract_displaced_skin,
+    extract_emissive_palette, strip_alpha_for_model, swap_jacket_back_and_tail,
+    upgrade_skin_if_needed,
+};
+
+/// A skin with every texture Ears draws it with, processed in the order Ears does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessedSkin {
+    /// The skin with its erase regions and displaced legs erased, alpha stripped and emissive
+    /// pixels moved to [`ProcessedSkin::emissive`]. A tail stored in the jacket back is swapped
+    /// back in place, so the skin is laid out like any other.
+    pub base: RgbaImage,
+    /// The leg pixels drawn on digitigrade legs instead of the base skin, laid out like it.
+    pub displaced: Option<RgbaImage>,
+    /// The emissive pixels of the base skin, drawn unlit.
+    pub emissive: Option<RgbaImage>,
+    /// The emissive pixels of the displaced legs.
+    pub displaced_emissive: Option<RgbaImage>,
+    pub features: Option<EarsFeatures>,
+    pub alfalfa: Option<AlfalfaData>,
+    /// The arm model the skin was processed for.
+    pub model: SkinModel,
+    /// The wing texture, if the skin has wings.
+    pub wing: Option<RgbaImage>,
+    /// The cape texture in Ears' format, if the skin has its cape enabled.
+    pub cape: Option<RgbaImage>,
+}
+
+impl ProcessedSkin {
+    /// Processes `image` like Ears does when it loads a skin:
+    ///
+    /// 1. Legacy 64x32 skins are upgraded.
+    /// 2. The features, Alfalfa data and emissive palette are read, before anything changes the
+    ///    pixels they are stored in.
+    /// 3. The jacket back and tail textures are swapped if the tail asks for it.
+    /// 4. Erase regions are erased.
+    /// 5. Displaced leg pixels are copied out, then erased from the base skin.
+    /// 6. Alpha is stripped from the parts that are forced opaque.
+    /// 7. Emissive pixels are split out of the base skin and the displaced legs.
+    ///
+    /// The arm model is detected with [`SkinModel::detect`]. The wing and cape textures are
+    /// stored as PNGs, so they are only decoded with the `png` feature, and left out without it.
+    pub fn from_image(image: &RgbaImage) -> Result<Self> {
+        Self::from_image_with_model(image, SkinModel::detect(image))
+    }
+
+    /// Processes `image` like [`ProcessedSkin::from_image`], for a player using `model`.
+    pub fn from_image_with_model(image: &RgbaImage, model: SkinModel) -> Result<Self> {
+        let base = upgrade_skin_if_needed(image.clone());
+        let alfalfa = read_alfalfa(&base)?;
+        Self::process(base, alfalfa, model)
+    }
+
+    /// Processes `base`, an upgraded skin, with the Alfalfa data already read from it.
+    pub(crate) fn process(
+        mut base: RgbaImage,
+        alfalfa: Option<AlfalfaData>,
+        model: SkinModel,
+    ) -> Result<Self> {
+        let features = EarsParser::parse(&base)?;
+        let palette = extract_emissive_palette(&base)?;
+
+        if features
+            .and_then(|features| features.tail)
+            .is_some_and(|tail| tail.swap_jacket_back)
+        {
+            swap_jacket_back_and_tail(&mut base);
+        }
+
+        if let Some(alfalfa) = &alfalfa {
+            apply_erase_regions(&mut base, alfalfa)?;
+        }
+
+        let mut displaced = None;
+        if let Some(features) = &features {
+            displaced = extract_displaced_skin(&base, features);
+            apply_erase_displaced_regions(&mut base, features)?;
+        }
+        strip_alpha_for_model(&mut base, features.as_ref(), model);
+
+        let (mut emissive, mut displaced_emissive) = (None, None);
+        if let Some(palette) = &palette {
+            emissive = Some(apply_emissive_palette(&mut base, palette)?);
+            if let Some(displaced) = &mut displaced {
+                displaced_emissive = Some(apply_emissive_palette(displaced, palette)?);
+            }
+        }
+
+        let decode =
+            |key| decode_entry(alfalfa.as_ref(), key, entry_enabled(features.as_ref(), key));
+        let wing = decode(AlfalfaDataKey::Wings);
+        let cape = decode(AlfalfaDataKey::Cape);
+
+        Ok(Self {
+            base,
+            displaced,
+            emissive,
+            displaced_emissive,
+            features,
+            alfalfa,
+            model,
+            wing,
+            cape,
+        })
+    }
+}
+
+/// Whether `features` draw the texture stored in the `key` entry.
+pub(crate) fn entry_enabled(features: Option<&EarsFeatures>, key: AlfalfaDataKey) -> bool {
+    features.is_some_and(|features| match key {
+        AlfalfaDataKey::Wings => features
+            .wing
+            .is_some_and(|wing| wing.mode != WingMode::None),
+        AlfalfaDataKey::Cape => features.cape_enabled,
+        AlfalfaDataKey::Erase | AlfalfaDataKey::Custom(_) => false,
+    })
+}
+
+/// Decodes the PNG stored in the `key` entry, if it is `enabled`. Entries that aren't valid PNGs
+/// are left out, like Ears leaves out textures it can't load.
+fn decode_entry(
+    alfalfa: Option<&AlfalfaData>,
+    key: AlfalfaDataKey,
+    enabled: bool,
+) -> Option<RgbaImage> {
+    let png = alfalfa?.get_data(key).filter(|_| enabled)?;
+    decode_png(png)
+}
+
+#[cfg(feature = "png")]
+fn decode_png(png: &[u8]) -> Option<RgbaImage> {
+    image::load_from_memory_with_format(png, image::ImageFormat::Png)
+        .ok()
+        .map(|image| image.to_rgba8())
+}
+
+#[cfg(not(feature = "png"))]
+fn decode_png(_png: &[u8]) -> Option<RgbaImage> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+    use crate::alfalfa::write_alfalfa;
+    use crate::features::data::wing::WingData;
+    use crate::parser::{EarsFeaturesWriter, v1::writer::EarsWriterV1};
+    use crate::utils::rescale_skin;
+
+    #[test]
+    fn matches_the_individual_steps() -> Result<()> {
+        let image = image::open("test_images/ears_v1_digitigrade_full_emissive_original.png")
+            .unwrap()
+            .to_rgba8();
+
+        let processed = ProcessedSkin::from_image(&image)?;
+
+        let features = processed.features.unwrap();
+        let palette = extract_emissive_palette(&image)?.unwrap();
+        let mut displaced = extract_displaced_skin(&image, &features).unwrap();
+        let displaced_emissive = apply_emissive_palette(&mut displaced, &palette)?;
+        assert_eq!(processed.displaced, Some(displaced));
+        assert_eq!(processed.displaced_emissive, Some(displaced_emissive));
+
+        // The displaced legs are erased from the base skin
+        assert_eq!(*processed.base.get_pixel(4, 20), Rgba([0, 0, 0, 0]));
+        assert!(processed.emissive.is_some());
+        assert_eq!(processed.wing, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn swaps_the_jacket_back_and_tail() -> Result<()> {
+        let image = image::open("test_images/ears_v1_tail_swap_original.png")
+            .unwrap()
+            .to_rgba8();
+        let swapped = image::open("test_images/ears_v1_tail_swap_swapped.png")
+            .unwrap()
+            .to_rgba8();
+
+        let processed = ProcessedSkin::from_image(&image)?;
+
+        assert!(processed.features.unwrap().tail.unwrap().swap_jacket_back);
+        assert_eq!(processed.base.get_pixel(56, 16), swapped.get_pixel(56, 16));
+
+        Ok(())
+    }
+
+    #[test]
+    fn processes_hd_digitigrade_legs() -> Result<()> {
+        let image = image::open("test_images/ears_v1_digitigrade_full_emissive_original.png")
+            .unwrap()
+            .to_rgba8();
+        let hd = rescale_skin(&image, 2)?;
+
+        let processed = ProcessedSkin::from_image(&image)?;
+        let processed_hd = ProcessedSkin::from_image(&hd)?;
+
+        for (texture, texture_hd) in [
+            (&processed.displaced, &processed_hd.displaced),
+            (
+                &processed.displaced_emissive,
+                &processed_hd.displaced_emissive,
+            ),
+        ] {
+            let (texture, texture_hd) = (texture.as_ref().unwrap(), texture_hd.as_ref().unwrap());
+            assert_eq!(texture_hd.dimensions(), (128, 128));
+            for (x, y, pixel) in texture_hd.enumerate_pixels() {
+                assert_eq!(pixel, texture.get_pixel(x / 2, y / 2), "({x}, {y})");
+            }
+        }
+        // The whole leg is erased, not just the part a 64x64 skin would have
+        for (x, y) in [(8, 40), (31, 63), (40, 104), (63, 127)] {
+            assert_eq!(
+                *processed_hd.base.get_pixel(x, y),
+                Rgba([0, 0, 0, 0]),
+                "({x}, {y})"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn leaves_out_textures_it_cannot_decode() -> Result<()> {
+        let mut image = RgbaImage::new(64, 64);
+        let features = EarsFeatures {
+            wing: Some(WingData::default()),
+            ..Default::default()
+        };
+        EarsWriterV1::write(&mut image, &features)?;
+        let mut alfalfa = AlfalfaData::new();
+        alfalfa.set_data(AlfalfaDataKey::Wings, vec![1, 2, 3]);
+        write_alfalfa(&alfalfa, &mut image)?;
+
+        let processed = ProcessedSkin::from_image(&image)?;
+
+        assert_eq!(processed.wing, None);
+        assert_eq!(processed.alfalfa, Some(alfalfa));
+
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "png")]
+    fn decodes_wing_and_cape_textures() -> Result<()> {
+        use std::io::Cursor;
+
+        use image::ImageFormat;
+
+        let mut image = RgbaImage::new(64, 64);
+        let features = EarsFeatures {
+            wing: Some(WingData::default()),
+            cape_enabled: true,
+            ..Default::default()
+        };
+        EarsWriterV1::write(&mut image, &features)?;
+
+        let png = |width, height| {
+            let mut png = Vec::new();
+            RgbaImage::from_pixel(width, height, Rgba([255, 0, 0, 255]))
+                .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+                .unwrap();
+            png
+        };
+        let mut alfalfa = AlfalfaData::new();
+        alfalfa.set_data(AlfalfaDataKey::Wings, png(20, 16));
+        alfalfa.set_data(AlfalfaDataKey::Cape, png(20, 16));
+        write_alfalfa(&alfalfa, &mut image)?;
+
+        let processed = ProcessedSkin::from_image(&image)?;
+
+        assert_eq!(processed.wing.map(|wing| wing.dimensions()), Some((20, 16)));
+        assert_eq!(processed.cape.map(|cape| cape.dimensions()), Some((20, 16)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn slim_arm_padding_stays_transparent() -> Result<()> {
+        let mut image = RgbaImage::new(64, 64);
+        for (x, y) in [(44, 20), (55, 20), (45, 52)] {
+            image.put_pixel(x, y, Rgba([10, 20, 30, 255]));
+        }
+        image.put_pixel(55, 20, Rgba([10, 20, 30, 0]));
+
+        let processed = ProcessedSkin::from_image(&image)?;
+        assert_eq!(processed.model, SkinModel::Slim);
+        assert_eq!(processed.base.get_pixel(55, 20)[3], 0);
+
+        let processed = ProcessedSkin::from_image_with_model(&image, SkinModel::Classic)?;
+        assert_eq!(processed.base.get_pixel(55, 20)[3], u8::MAX);
+
+        Ok(())
+    }
+}
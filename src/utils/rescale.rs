@@ -0,0 +1,153 @@
+use image::{Rgba, RgbaImage};
+
+use crate::alfalfa::{read_alfalfa, write_alfalfa};
+use crate::layout::skin_scale;
+use crate::parser::{EarsParser, write_features};
+use crate::utils::errors::{EarsError, Result};
+use crate::utils::{extract_emissive_palette, write_emissive_palette};
+
+/// Rescales `image` to `target_scale` times the size of a 64x64 skin, keeping its Ears data.
+///
+/// Texels are upscaled with nearest neighbour. When downscaling, every texel takes the colour
+/// found most often in the block of texels it replaces, the top left one winning ties. The
+/// feature block, emissive palette and Alfalfa data are then written again at the new size, so
+/// they still read the same.
+pub fn rescale_skin(image: &RgbaImage, target_scale: u32) -> Result<RgbaImage> {
+    let Some(scale) = skin_scale(image) else {
+        return Err(EarsError::InvalidSkinSize(image.width(), image.height()));
+    };
+    if target_scale == 0 {
+        return Err(EarsError::InvalidSkinScale(target_scale));
+    }
+
+    // Read everything before any texel changes
+    let features = EarsParser::parse(image)?;
+    let palette = extract_emissive_palette(image)?;
+    let alfalfa = read_alfalfa(image)?;
+
+    let size = 64 * target_scale;
+    let mut rescaled = RgbaImage::from_fn(size, size, |x, y| {
+        if target_scale >= scale {
+            *image.get_pixel(x * scale / target_scale, y * scale / target_scale)
+        } else {
+            representative_texel(image, x, y, scale, target_scale)
+        }
+    });
+
+    if let Some(features) = &features {
+        write_features(&mut rescaled, features)?;
+    }
+    if let Some(palette) = &palette {
+        write_emissive_palette(&mut rescaled, palette)?;
+    }
+    if let Some(alfalfa) = &alfalfa {
+        // Every encoded texel is written again, so nothing of the old payload is left behind
+        write_alfalfa(alfalfa, &mut rescaled)?;
+    }
+
+    Ok(rescaled)
+}
+
+/// Returns the colour found most often in the texels of `image` that the texel at `(x, y)` of
+/// the downscaled skin covers.
+fn representative_texel(image: &RgbaImage, x: u32, y: u32, scale: u32, target: u32) -> Rgba<u8> {
+    let (x1, x2) = (x * scale / target, ((x + 1) * scale).div_ceil(target));
+    let (y1, y2) = (y * scale / target, ((y + 1) * scale).div_ceil(target));
+
+    let mut counts: Vec<(Rgba<u8>, u32)> = Vec::new();
+    for y in y1..y2 {
+        for x in x1..x2 {
+            let pixel = *image.get_pixel(x, y);
+            match counts.iter_mut().find(|(colour, _)| *colour == pixel) {
+                Some((_, count)) => *count += 1,
+                None => counts.push((pixel, 1)),
+            }
+        }
+    }
+
+    // The first colour seen wins ties, as max_by_key would pick the last one
+    let mut best = counts[0];
+    for &(colour, count) in &counts[1..] {
+        if count > best.1 {
+            best = (colour, count);
+        }
+    }
+    best.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::upgrade_skin_if_needed;
+
+    #[test]
+    fn rescaling_keeps_ears_data() -> Result<()> {
+        for path in [
+            "test_images/ears_v1_nickac_sample.png",
+            "test_images/emissive-before.png",
+        ] {
+            let image = image::open(path).unwrap().to_rgba8();
+            let features = EarsParser::parse(&image)?;
+            let palette = extract_emissive_palette(&image)?;
+            let alfalfa = read_alfalfa(&image)?;
+            assert!(features.is_some());
+
+            for target in [2, 3] {
+                let hd = rescale_skin(&image, target)?;
+                assert_eq!(hd.dimensions(), (64 * target, 64 * target));
+                assert_eq!(
+                    *hd.get_pixel(9 * target, 9 * target),
+                    *image.get_pixel(9, 9)
+                );
+
+                let back = rescale_skin(&hd, 1)?;
+                for skin in [&hd, &back] {
+                    assert_eq!(EarsParser::parse(skin)?, features, "{path} at {target}x");
+                    assert_eq!(extract_emissive_palette(skin)?, palette);
+                    assert_eq!(read_alfalfa(skin)?, alfalfa);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn downscaling_picks_the_most_common_texel() -> Result<()> {
+        let image = upgrade_skin_if_needed(
+            image::open("test_images/notch_original.png")
+                .unwrap()
+                .to_rgba8(),
+        );
+        let mut hd = rescale_skin(&image, 2)?;
+
+        let (red, blue) = (Rgba([255, 0, 0, 255]), Rgba([0, 0, 255, 255]));
+        for (x, y) in [(16, 16), (17, 16), (16, 17)] {
+            hd.put_pixel(x, y, red);
+        }
+        hd.put_pixel(17, 17, blue);
+        // Ties go to the top left texel
+        for (x, y) in [(18, 16), (18, 17)] {
+            hd.put_pixel(x, y, blue);
+        }
+        for (x, y) in [(19, 16), (19, 17)] {
+            hd.put_pixel(x, y, red);
+        }
+
+        let downscaled = rescale_skin(&hd, 1)?;
+        assert_eq!(*downscaled.get_pixel(8, 8), red);
+        assert_eq!(*downscaled.get_pixel(9, 8), blue);
+        assert_eq!(*downscaled.get_pixel(10, 10), *image.get_pixel(10, 10));
+
+        assert!(matches!(
+            rescale_skin(&RgbaImage::new(64, 32), 2),
+            Err(EarsError::InvalidSkinSize(64, 32))
+        ));
+        assert!(matches!(
+            rescale_skin(&image, 0),
+            Err(EarsError::InvalidSkinScale(0))
+        ));
+
+        Ok(())
+    }
+}
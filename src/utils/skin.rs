@@ -2,24 +2,19 @@ use image::RgbaImage;
 
 use crate::{
     features::{EarsFeatures, data::leg::LegMode},
-    utils::alpha::{LEG_BOTTOM_HALF_REGIONS, LEG_REGIONS, Rectangle},
+    layout::{TextureRegion, skin_scale},
+    utils::alpha::displaced_leg_regions,
 };
 
 pub fn apply_erase_displaced_regions(
     image: &mut RgbaImage,
     features: &EarsFeatures,
 ) -> crate::utils::errors::Result<()> {
-    let regions = match features.leg_mode {
-        LegMode::Plantigrade => return Ok(()),
-        LegMode::DigitigradePartial => LEG_BOTTOM_HALF_REGIONS,
-        LegMode::DigitigradeFull => LEG_REGIONS,
-    };
-    for region in regions {
-        for y in region.y1..region.y2 {
-            for x in region.x1..region.x2 {
-                if let Some(pixel) = image.get_pixel_mut_checked(x, y) {
-                    *pixel = image::Rgba([0, 0, 0, 0]);
-                }
+    let scale = skin_scale(image).unwrap_or(1);
+    for (region, _) in displaced_leg_regions(features.leg_mode) {
+        for (x, y) in region.scaled(scale).pixels() {
+            if let Some(pixel) = image.get_pixel_mut_checked(x, y) {
+                *pixel = image::Rgba([0, 0, 0, 0]);
             }
         }
     }
@@ -27,28 +22,29 @@ pub fn apply_erase_displaced_regions(
 }
 
 pub fn extract_displaced_skin(image: &RgbaImage, features: &EarsFeatures) -> Option<RgbaImage> {
-    let regions = match features.leg_mode {
-        LegMode::Plantigrade => return None,
-        LegMode::DigitigradePartial => LEG_BOTTOM_HALF_REGIONS,
-        LegMode::DigitigradeFull => LEG_REGIONS,
-    };
+    if features.leg_mode == LegMode::Plantigrade {
+        return None;
+    }
 
-    Some(copy_displaced_regions(image, regions))
+    Some(copy_displaced_regions(
+        image,
+        &displaced_leg_regions(features.leg_mode),
+    ))
 }
 
-fn copy_displaced_regions(image: &RgbaImage, regions: &[Rectangle]) -> RgbaImage {
-    let mut displaced = RgbaImage::new(64, 64);
-    for region in regions {
-        for y in region.y1..region.y2 {
-            for x in region.x1..region.x2 {
-                let Some(mut pixel) = image.get_pixel_checked(x, y).copied() else {
-                    continue;
-                };
-                if region.force_opaque {
-                    pixel.0[3] = u8::MAX;
-                }
-                displaced.put_pixel(x, y, pixel);
+/// Copies `regions` of `image` to an empty texture of the same size, scaling them for HD skins.
+fn copy_displaced_regions(image: &RgbaImage, regions: &[(TextureRegion, bool)]) -> RgbaImage {
+    let scale = skin_scale(image).unwrap_or(1);
+    let mut displaced = RgbaImage::new(64 * scale, 64 * scale);
+    for &(region, force_opaque) in regions {
+        for (x, y) in region.scaled(scale).pixels() {
+            let Some(mut pixel) = image.get_pixel_checked(x, y).copied() else {
+                continue;
+            };
+            if force_opaque {
+                pixel.0[3] = u8::MAX;
             }
+            displaced.put_pixel(x, y, pixel);
         }
     }
     displaced
@@ -0,0 +1,95 @@
+use image::RgbaImage;
+
+use crate::layout::{BodyPart, Face, Layer, face_region, layer_regions, skin_scale};
+
+/// The arm model a skin is worn with, which the skin itself doesn't store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SkinModel {
+    /// 4 pixel wide arms, also known as Steve.
+    #[default]
+    Classic,
+    /// 3 pixel wide arms, also known as Alex.
+    Slim,
+}
+
+impl SkinModel {
+    /// Guesses the model of `image` from its right arm: slim skins leave the texture their arms
+    /// don't use transparent, while drawing the front of the arm. Legacy 64x32 skins, and skins
+    /// whose arm is fully transparent, are classic.
+    pub fn detect(image: &RgbaImage) -> Self {
+        let Some(scale) = skin_scale(image) else {
+            return Self::Classic;
+        };
+        let visible = |(x, y)| image.get_pixel(x, y)[3] != 0;
+
+        let slim = layer_regions(BodyPart::RightArm, Layer::Base, SkinModel::Slim)
+            .map(|region| region.scaled(scale));
+        let padding_is_empty = layer_regions(BodyPart::RightArm, Layer::Base, SkinModel::Classic)
+            .iter()
+            .flat_map(|region| region.scaled(scale).pixels())
+            .filter(|&(x, y)| !slim.iter().any(|region| region.contains(x, y)))
+            .all(|pixel| !visible(pixel));
+        let front = face_region(
+            BodyPart::RightArm,
+            Layer::Base,
+            Face::Front,
+            SkinModel::Slim,
+        );
+
+        if padding_is_empty && front.scaled(scale).pixels().any(visible) {
+            Self::Slim
+        } else {
+            Self::Classic
+        }
+    }
+
+    /// How many pixels wide the arms are.
+    pub fn arm_width(&self) -> u32 {
+        match self {
+            SkinModel::Classic => 4,
+            SkinModel::Slim => 3,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{Rgba, imageops};
+
+    use super::*;
+    use crate::layout::TextureRegion;
+
+    #[test]
+    fn detects_slim_skins() {
+        let image = image::open("test_images/mister_fix_upgraded.png")
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(SkinModel::detect(&image), SkinModel::Classic);
+
+        // Arms without any opaque pixel say nothing about the model
+        let transparent = image::open("test_images/notch_upgraded.png")
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(SkinModel::detect(&transparent), SkinModel::Classic);
+
+        let mut slim = image.clone();
+        for (x, y) in [
+            TextureRegion::new(50, 16, 2, 4),
+            TextureRegion::new(54, 20, 2, 12),
+        ]
+        .iter()
+        .flat_map(TextureRegion::pixels)
+        {
+            slim.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+        }
+        assert_eq!(SkinModel::detect(&slim), SkinModel::Slim);
+
+        let hd = imageops::resize(&slim, 128, 128, imageops::FilterType::Nearest);
+        assert_eq!(SkinModel::detect(&hd), SkinModel::Slim);
+
+        let legacy = image::open("test_images/notch_original.png")
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(SkinModel::detect(&legacy), SkinModel::Classic);
+    }
+}